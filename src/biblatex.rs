@@ -1,10 +1,41 @@
 use super::*;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
-pub struct FmtBiblatex<'a, T: ?Sized>(pub &'a T);
+/// A value formatted as BibLaTeX, with the policy for characters which have no known LaTeX
+/// equivalent (see [`Utf8ToTex::on_unknown`]).
+pub struct FmtBiblatex<'a, T: ?Sized>(pub &'a T, pub UnknownCharPolicy);
+
+impl<'a, T: ?Sized> FmtBiblatex<'a, T> {
+    /// Set the policy for characters which have no known LaTeX equivalent.
+    pub fn on_unknown(self, policy: UnknownCharPolicy) -> Self {
+        FmtBiblatex(self.0, policy)
+    }
+}
 
 pub trait ToBiblatex {
     fn biblatex<'a>(&'a self) -> FmtBiblatex<'a, Self>;
+
+    /// Whether the value is a macro name, which must be written without enclosing braces.
+    fn is_macro(&self) -> bool {
+        false
+    }
+
+    /// Whether the value is empty, in which case the field is left out of an entry.
+    fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// How the value breaks BibLaTeX's constraints on it, if it does, e.g. a month which is not
+    /// between 1 and 12.
+    fn violation(&self) -> Option<String> {
+        None
+    }
+
+    /// Why the value is probably a mistake, although BibLaTeX accepts it, e.g. a page range which
+    /// ends before it starts.
+    fn warning(&self) -> Option<String> {
+        None
+    }
 }
 
 macro_rules! impl_tobiblatex {
@@ -12,13 +43,15 @@ macro_rules! impl_tobiblatex {
         $(
             impl ToBiblatex for $t {
                 fn biblatex<'a>(&'a self) -> FmtBiblatex<'a, Self> {
-                    FmtBiblatex(self)
+                    FmtBiblatex(self, UnknownCharPolicy::default())
                 }
             }
         )*
     };
 }
 
+pub use entry::{entry_keys, Bibliography, FieldNames};
+
 pub mod types {
     use super::*;
 
@@ -30,9 +63,42 @@ pub mod types {
         }
     }
 
-    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    /// A year, or a literal such as `Forthcoming` for a date without one, which BibLaTeX accepts
+    /// in the `year` field.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum Year {
+        Number(Int),
+        Literal(Literal),
+    }
+
+    impl Year {
+        /// The year as a number, if it isn't a literal
+        pub fn number(&self) -> Option<Int> {
+            match self {
+                Year::Number(y) => Some(*y),
+                Year::Literal(_) => None,
+            }
+        }
+    }
+
+    impl From<Int> for Year {
+        fn from(year: Int) -> Self {
+            Year::Number(year)
+        }
+    }
+
+    impl<'a> Display for FmtBiblatex<'a, Year> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+            match self.0 {
+                Year::Number(y) => write!(f, "{}", y),
+                Year::Literal(l) => l.biblatex().on_unknown(self.1).fmt(f),
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
     pub struct Date {
-        pub year: Int,
+        pub year: Year,
         pub month: Option<Int>,
         pub day: Option<Int>,
     }
@@ -40,7 +106,7 @@ pub mod types {
     impl Date {
         pub fn year(year: Int) -> Self {
             Date {
-                year,
+                year: year.into(),
                 month: None,
                 day: None,
             }
@@ -48,7 +114,7 @@ pub mod types {
 
         pub fn year_month(year: Int, month: Int) -> Self {
             Date {
-                year,
+                year: year.into(),
                 month: Some(month),
                 day: None,
             }
@@ -56,27 +122,134 @@ pub mod types {
 
         pub fn full(year: Int, month: Int, day: Int) -> Self {
             Date {
-                year,
+                year: year.into(),
                 month: Some(month),
                 day: Some(day),
             }
         }
+
+        /// The season, if the month is one of the BibLaTeX season codes: 21 (spring), 22
+        /// (summer), 23 (autumn) or 24 (winter).
+        pub fn season(&self) -> Option<Int> {
+            self.month.filter(|m| (21..=24).contains(m))
+        }
+
+        /// Convert to a CSL date variable, `{"date-parts": [[year, month, day]]}`.  Trailing
+        /// components which are `None` are omitted.  A literal year becomes `{"literal": year}`.
+        pub fn to_csl_date_parts(&self) -> serde_json::Value {
+            let year = match &self.year {
+                Year::Number(y) => *y,
+                Year::Literal(l) => return serde_json::json!({ "literal": l.0 }),
+            };
+            let mut parts = vec![year];
+            if let Some(m) = self.month {
+                parts.push(m);
+                if let Some(d) = self.day {
+                    parts.push(d);
+                }
+            }
+            serde_json::json!({ "date-parts": [parts] })
+        }
     }
 
     impl<'a> Display for FmtBiblatex<'a, Date> {
+        /// Formats as an ISO 8601 `YYYY-MM-DD` date, with the month and day as precision allows
         fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-            write!(f, "{}", self.0.year)?;
+            self.0.year.biblatex().on_unknown(self.1).fmt(f)?;
             if let Some(m) = self.0.month {
-                write!(f, "-{}", m)?;
+                write!(f, "-{:02}", m)?;
                 if let Some(d) = self.0.day {
-                    write!(f, "-{}", d)?;
+                    write!(f, "-{:02}", d)?;
                 }
             }
             Ok(())
         }
     }
 
-    #[derive(Clone, Copy, Debug)]
+    impl ToBiblatex for Date {
+        fn biblatex<'a>(&'a self) -> FmtBiblatex<'a, Self> {
+            FmtBiblatex(self, UnknownCharPolicy::default())
+        }
+
+        fn violation(&self) -> Option<String> {
+            if let Year::Literal(l) = &self.year {
+                return Some(format!("`{}` is not a date", l.0));
+            }
+            match (self.month, self.day) {
+                (Some(m), _) if !(1..=12).contains(&m) && self.season().is_none() => {
+                    Some(format!("month {} is not between 1 and 12", m))
+                }
+                (_, Some(d)) if !(1..=31).contains(&d) => {
+                    Some(format!("day {} is not between 1 and 31", d))
+                }
+                _ => None,
+            }
+        }
+    }
+
+    /// A month, written either as a number or as a BibLaTeX month macro (`jan`, `feb`, ...)
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Month {
+        pub number: Int,
+        macro_form: bool,
+    }
+
+    impl Month {
+        const MACROS: [&'static str; 12] = [
+            "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+        ];
+
+        /// The month macro, if the month is between 1 and 12
+        pub fn macro_name(&self) -> Option<&'static str> {
+            let i = usize::try_from(self.number).ok()?.checked_sub(1)?;
+            Self::MACROS.get(i).copied()
+        }
+
+        /// Write the month as a macro.  Months without a macro name are still written as numbers.
+        pub fn as_macro(self) -> Self {
+            Month {
+                macro_form: true,
+                ..self
+            }
+        }
+    }
+
+    impl From<Int> for Month {
+        fn from(number: Int) -> Self {
+            Month {
+                number,
+                macro_form: false,
+            }
+        }
+    }
+
+    impl ToBiblatex for Month {
+        fn biblatex<'a>(&'a self) -> FmtBiblatex<'a, Self> {
+            FmtBiblatex(self, UnknownCharPolicy::default())
+        }
+
+        fn is_macro(&self) -> bool {
+            self.macro_form && self.macro_name().is_some()
+        }
+
+        fn violation(&self) -> Option<String> {
+            match self.number {
+                1..=12 => None,
+                n => Some(format!("month {} is not between 1 and 12", n)),
+            }
+        }
+    }
+
+    impl<'a> Display for FmtBiblatex<'a, Month> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+            match self.0.macro_name() {
+                Some(name) if self.0.macro_form => f.write_str(name),
+                _ => write!(f, "{}", self.0.number),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub enum PubState {
         /// The manuscript is being prepared for publication
         InPreparation,
@@ -104,8 +277,9 @@ pub mod types {
     }
 
     macro_rules! tranparent_string_wrapper {
-        ($name:ident) => {
-            #[derive(Clone, Debug)]
+        ($(#[$attr:meta])* $name:ident) => {
+            $(#[$attr])*
+            #[derive(Clone, Debug, PartialEq, Eq)]
             pub struct $name(pub String);
 
             impl From<String> for $name {
@@ -124,9 +298,99 @@ pub mod types {
 
     tranparent_string_wrapper!(Literal);
 
+    tranparent_string_wrapper!(
+        /// A literal for free-form fields like `note`, which often hold links.  URLs are wrapped
+        /// in `\url{...}` rather than escaped, so they survive as working links.
+        Note
+    );
+
+    lazy_static::lazy_static! {
+        static ref URL: regex::Regex =
+            regex::Regex::new(r#"(?:https?|ftp)://[^\s{}<>]*[^\s{}<>.,;:!?)\]'"]"#).unwrap();
+        static ref FORMATTING_TAG: regex::Regex = regex::Regex::new(r"<(/?)(i|b|sub|sup)>").unwrap();
+    }
+
+    /// Escape `s`, wrapping URLs in `\url{...}` if `urls` is set.  `&lt;` and `&gt;` are
+    /// written as `<` and `>`.
+    fn write_text(
+        f: &mut Formatter<'_>,
+        s: &str,
+        on_unknown: UnknownCharPolicy,
+        urls: bool,
+    ) -> FmtResult {
+        let s = &s.replace("&lt;", "<").replace("&gt;", ">");
+        if !urls {
+            return utf8_to_tex(s).on_unknown(on_unknown).fmt(f);
+        }
+        let mut last = 0;
+        for m in URL.find_iter(s) {
+            utf8_to_tex(&s[last..m.start()])
+                .on_unknown(on_unknown)
+                .fmt(f)?;
+            let url = m.as_str().replace('%', r"\%").replace('#', r"\#");
+            write!(f, r"\url{{{}}}", url)?;
+            last = m.end();
+        }
+        utf8_to_tex(&s[last..]).on_unknown(on_unknown).fmt(f)
+    }
+
+    /// Whether every formatting tag in `s` is closed, in the right order
+    fn tags_balanced(s: &str) -> bool {
+        let mut open = Vec::new();
+        for c in FORMATTING_TAG.captures_iter(s) {
+            if c[1].is_empty() {
+                open.push(c.get(2).unwrap().as_str());
+            } else if open.pop() != Some(&c[2]) {
+                return false;
+            }
+        }
+        open.is_empty()
+    }
+
+    /// Escape `s`, converting the CSL formatting tags `<i>`, `<b>`, `<sub>` and `<sup>` to
+    /// `\textit{...}`, `\textbf{...}`, `\textsubscript{...}` and `\textsuperscript{...}`, unless
+    /// they are unbalanced, in which case they are escaped like any other text.  Angle brackets
+    /// escaped as `&lt;` and `&gt;` (see [`crate::strip_markup`]) are never read as tags.
+    fn write_literal(
+        f: &mut Formatter<'_>,
+        s: &str,
+        on_unknown: UnknownCharPolicy,
+        urls: bool,
+    ) -> FmtResult {
+        if !tags_balanced(s) {
+            return write_text(f, s, on_unknown, urls);
+        }
+        let mut last = 0;
+        for c in FORMATTING_TAG.captures_iter(s) {
+            let m = c.get(0).unwrap();
+            write_text(f, &s[last..m.start()], on_unknown, urls)?;
+            if c[1].is_empty() {
+                let command = match &c[2] {
+                    "i" => r"\textit{",
+                    "b" => r"\textbf{",
+                    "sub" => r"\textsubscript{",
+                    _ => r"\textsuperscript{",
+                };
+                f.write_str(command)?;
+            } else {
+                f.write_str("}")?;
+            }
+            last = m.end();
+        }
+        write_text(f, &s[last..], on_unknown, urls)
+    }
+
     impl<'a> Display for FmtBiblatex<'a, Literal> {
+        /// See [`write_literal`]
+        fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+            write_literal(f, &self.0 .0, self.1, false)
+        }
+    }
+
+    impl<'a> Display for FmtBiblatex<'a, Note> {
+        /// Like a [`Literal`], but with URLs wrapped in `\url{...}`
         fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-            crate::escape::utf8_to_tex(&self.0 .0).fmt(f)
+            write_literal(f, &self.0 .0, self.1, true)
         }
     }
 
@@ -146,10 +410,34 @@ pub mod types {
         }
     }
 
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, PartialEq, Eq)]
     pub enum Range {
         Single(Int),
-        Multi { start: Int, end: Option<Int> },
+        Multi {
+            start: Int,
+            end: Option<Int>,
+        },
+        /// A range with no start, such as `-20`
+        UpTo(Int),
+        /// Discontinuous pages, such as `12, 15-18, 22`
+        List(Vec<Range>),
+    }
+
+    impl ToBiblatex for Range {
+        fn biblatex<'a>(&'a self) -> FmtBiblatex<'a, Self> {
+            FmtBiblatex(self, UnknownCharPolicy::default())
+        }
+
+        fn warning(&self) -> Option<String> {
+            match self {
+                Range::Multi {
+                    start,
+                    end: Some(end),
+                } if end < start => Some(format!("range {}-{} ends before it starts", start, end)),
+                Range::List(ranges) => ranges.iter().find_map(Range::warning),
+                _ => None,
+            }
+        }
     }
 
     impl<'a> Display for FmtBiblatex<'a, Range> {
@@ -161,30 +449,295 @@ pub mod types {
                     end: Some(end),
                 } => write!(f, "{}-{}", start, end),
                 Range::Multi { start, end: None } => write!(f, "{}-", start),
+                Range::UpTo(end) => write!(f, "-{}", end),
+                Range::List(ranges) => {
+                    for (i, r) in ranges.iter().enumerate() {
+                        if i > 0 {
+                            f.write_str(", ")?;
+                        }
+                        r.biblatex().fmt(f)?;
+                    }
+                    Ok(())
+                }
             }
         }
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq, Eq)]
     pub struct Name {
         given: String,
         family: String,
+        /// The "von" part of the name, eg "van der" in "van der Berg"
+        particle: Option<String>,
+        /// The "Jr" part of the name
+        suffix: Option<String>,
+        /// A name which should not be split into parts, such as an organization
+        literal: Option<String>,
+        /// Whether the family name is written first, as in "Mao Zedong"
+        family_first: bool,
+        /// The special name `others`, which biber renders as "et al."
+        others: bool,
     }
 
     impl Name {
         pub fn new(given: String, family: String) -> Self {
-            Name { given, family }
+            Name {
+                given,
+                family,
+                particle: None,
+                suffix: None,
+                literal: None,
+                family_first: false,
+                others: false,
+            }
         }
+
+        pub fn literal(name: String) -> Self {
+            Name {
+                given: String::new(),
+                family: String::new(),
+                particle: None,
+                suffix: None,
+                literal: Some(name),
+                family_first: false,
+                others: false,
+            }
+        }
+
+        /// The special name `others`, which ends a truncated name list and is rendered as
+        /// "et al."
+        pub fn others() -> Self {
+            Name {
+                others: true,
+                ..Name::literal(String::new())
+            }
+        }
+
+        pub fn is_others(&self) -> bool {
+            self.others
+        }
+
+        pub fn with_particle(mut self, particle: String) -> Self {
+            self.particle = Some(particle);
+            self
+        }
+
+        pub fn with_suffix(mut self, suffix: String) -> Self {
+            self.suffix = Some(suffix);
+            self
+        }
+
+        /// Write the family name first, as is usual for Chinese, Japanese and Korean names.  The
+        /// name is brace-protected in this order, since `Family, Given` would be displayed as
+        /// "Given Family".
+        pub fn with_family_first(mut self) -> Self {
+            self.family_first = true;
+            self
+        }
+
+        /// Parse a name written in either the "Given von Family" or "von Family, Jr, Given"
+        /// convention, like BibTeX does.  Particles are the lowercase words before the family
+        /// name, and a name wrapped in braces is kept as a literal.  This is a best-effort split
+        /// for plain-text input; names from CSL should use the structured fields instead.
+        pub fn parse(s: &str) -> Name {
+            let s = s.trim();
+            if s == "others" {
+                return Name::others();
+            }
+            if is_braced(s) {
+                return Name::literal(s[1..s.len() - 1].to_string());
+            }
+
+            fn is_particle(word: &str) -> bool {
+                word.starts_with(|c: char| c.is_lowercase())
+            }
+
+            // Splits "von Family" into the particle and family name
+            fn split_particle(words: &[&str]) -> (Option<String>, String) {
+                let n = words
+                    .iter()
+                    .take(words.len().saturating_sub(1))
+                    .take_while(|w| is_particle(w))
+                    .count();
+                let particle = (n > 0).then(|| words[..n].join(" "));
+                (particle, words[n..].join(" "))
+            }
+
+            let parts: Vec<_> = split_top_level(s, |c| c == ',')
+                .into_iter()
+                .map(str::trim)
+                .collect();
+            let (particle, family, suffix, given) = match parts.as_slice() {
+                [von_last, jr, first, ..] => {
+                    let (particle, family) = split_particle(&split_words(von_last));
+                    (particle, family, Some(jr.to_string()), first.to_string())
+                }
+                [von_last, first] => {
+                    let (particle, family) = split_particle(&split_words(von_last));
+                    (particle, family, None, first.to_string())
+                }
+                _ => {
+                    const SUFFIXES: &[&str] = &["Jr", "Jr.", "Sr", "Sr.", "II", "III", "IV"];
+                    let mut words = split_words(s);
+                    let suffix = match words.last() {
+                        Some(w) if words.len() > 2 && SUFFIXES.contains(w) => words.pop(),
+                        _ => None,
+                    };
+                    let first_particle = words
+                        .iter()
+                        .take(words.len().saturating_sub(1))
+                        .position(|w| is_particle(w))
+                        .unwrap_or_else(|| words.len().saturating_sub(1));
+                    let (particle, family) = split_particle(&words[first_particle..]);
+                    (
+                        particle,
+                        family,
+                        suffix.map(String::from),
+                        words[..first_particle].join(" "),
+                    )
+                }
+            };
+
+            Name {
+                given,
+                family,
+                particle,
+                suffix: suffix.filter(|s| !s.is_empty()),
+                literal: None,
+                family_first: false,
+                others: false,
+            }
+        }
+
+        /// Parse a list of names separated by `and`, as in a BibLaTeX name list.
+        pub fn parse_list(s: &str) -> List<Name> {
+            let mut names = Vec::new();
+            let mut rest = s;
+            while let Some(i) = find_top_level_and(rest) {
+                names.push(Name::parse(&rest[..i]));
+                rest = &rest[i + " and ".len()..];
+            }
+            if !rest.trim().is_empty() {
+                names.push(Name::parse(rest));
+            }
+            List(names)
+        }
+
+        /// The name as a single unit, if it is a literal name or a family name only (which is
+        /// how corporate authors are often stored).
+        fn literal_form(&self) -> Option<&str> {
+            if let Some(literal) = &self.literal {
+                return Some(literal);
+            }
+            let family_only =
+                self.given.trim().is_empty() && self.particle.is_none() && self.suffix.is_none();
+            family_only.then_some(self.family.as_str())
+        }
+    }
+
+    /// Split `s` at the characters matching `sep` which are not inside braces.
+    fn split_top_level(s: &str, sep: impl Fn(char) -> bool) -> Vec<&str> {
+        let mut parts = Vec::new();
+        let mut depth = 0usize;
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth = depth.saturating_sub(1),
+                c if depth == 0 && sep(c) => {
+                    parts.push(&s[start..i]);
+                    start = i + c.len_utf8();
+                }
+                _ => {}
+            }
+        }
+        parts.push(&s[start..]);
+        parts
+    }
+
+    /// Whether `s` is a single brace group, like `{World Health Organization}`
+    fn is_braced(s: &str) -> bool {
+        if !s.starts_with('{') {
+            return false;
+        }
+        let mut depth = 0usize;
+        for (i, c) in s.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        return i == s.len() - 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+
+    fn split_words(s: &str) -> Vec<&str> {
+        split_top_level(s, char::is_whitespace)
+            .into_iter()
+            .filter(|w| !w.is_empty())
+            .collect()
+    }
+
+    /// The byte offset of the first ` and ` which is not inside braces
+    fn find_top_level_and(s: &str) -> Option<usize> {
+        let mut depth = 0usize;
+        for (i, c) in s.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth = depth.saturating_sub(1),
+                ' ' if depth == 0 && s[i..].starts_with(" and ") => return Some(i),
+                _ => {}
+            }
+        }
+        None
     }
 
     impl<'a> Display for FmtBiblatex<'a, Name> {
+        /// Formats as `von Last, Jr, First`, `{Literal}`, or `{von Last First Jr}` for names
+        /// written family name first
         fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
             let n = self.0;
-            write!(f, "{}, {}", utf8_to_tex(&n.family), utf8_to_tex(&n.given),)
+            let tex = |s| utf8_to_tex(s).on_unknown(self.1);
+            if n.others {
+                return f.write_str("others");
+            }
+            if let Some(literal) = n.literal_form() {
+                return write!(f, "{{{}}}", tex(literal));
+            }
+            if n.family_first {
+                let parts = [
+                    n.particle.as_deref(),
+                    Some(&n.family),
+                    Some(&n.given),
+                    n.suffix.as_deref(),
+                ];
+                let words = parts.into_iter().flatten().filter(|w| !w.trim().is_empty());
+                f.write_str("{")?;
+                for (i, w) in words.enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    tex(w).fmt(f)?;
+                }
+                return f.write_str("}");
+            }
+            if let Some(particle) = &n.particle {
+                write!(f, "{} ", tex(particle))?;
+            }
+            write!(f, "{}", tex(&n.family))?;
+            if let Some(suffix) = &n.suffix {
+                write!(f, ", {}", tex(suffix))?;
+            }
+            write!(f, ", {}", tex(&n.given))
         }
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq, Eq)]
     pub struct List<T>(pub Vec<T>);
 
     impl<T> List<T> {
@@ -193,9 +746,45 @@ pub mod types {
         }
     }
 
-    impl<T> ToBiblatex for List<T> {
+    impl List<Name> {
+        /// Keep only the first `max` names, ending the list with `others` if any were removed.
+        pub fn truncate_with_others(&mut self, max: usize) {
+            let names = &mut self.0;
+            let mut others = matches!(names.last(), Some(n) if n.others);
+            if others {
+                names.pop();
+            }
+            if names.len() > max {
+                names.truncate(max);
+                others = true;
+            }
+            if others {
+                names.push(Name::others());
+            }
+        }
+    }
+
+    impl<T> From<Vec<T>> for List<T> {
+        fn from(v: Vec<T>) -> Self {
+            List(v)
+        }
+    }
+
+    impl<T: ToBiblatex> ToBiblatex for List<T> {
         fn biblatex<'a>(&'a self) -> FmtBiblatex<'a, Self> {
-            FmtBiblatex(self)
+            FmtBiblatex(self, UnknownCharPolicy::default())
+        }
+
+        fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+
+        fn violation(&self) -> Option<String> {
+            self.0.iter().find_map(T::violation)
+        }
+
+        fn warning(&self) -> Option<String> {
+            self.0.iter().find_map(T::warning)
         }
     }
 
@@ -207,24 +796,33 @@ pub mod types {
         fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
             let mut values = self.0 .0.iter();
             if let Some(first) = values.next() {
-                write!(f, "{}", first.biblatex())?;
+                write!(f, "{}", first.biblatex().on_unknown(self.1))?;
             }
             for v in values {
-                write!(f, " and {}", v.biblatex())?;
+                write!(f, " and {}", v.biblatex().on_unknown(self.1))?;
             }
             Ok(())
         }
     }
 
+    impl ToBiblatex for Literal {
+        fn biblatex<'a>(&'a self) -> FmtBiblatex<'a, Self> {
+            FmtBiblatex(self, UnknownCharPolicy::default())
+        }
+
+        fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+    }
+
     impl_tobiblatex! {
-        Date,
         Int,
-        Literal,
         Name,
+        Note,
         PubState,
-        Range,
         Uri,
         Verbatim,
+        Year,
     }
 }
 
@@ -235,7 +833,8 @@ pub mod field {
 
     #[rustfmt::skip]
     macro_rules! field_ty {
-        (addendum) => { Literal };
+        (abstract_) => { Literal };
+        (addendum) => { Note };
         (annotator) => { List<Name> };
         (author) => { List<Name> };
         (book_title) => { Literal };
@@ -243,6 +842,8 @@ pub mod field {
         (book_subtitle) => { Literal };
         (chapter) => { Literal };
         (commentator) => { List<Name> };
+        (crossref) => { Verbatim };
+        (date) => { Date };
         (doi) => { Verbatim };
         (edition) => { Literal };
         (editor) => { List<Name> };
@@ -250,32 +851,40 @@ pub mod field {
         (eprint) => { Verbatim };
         (eprint_class) => { Literal };
         (eprint_type) => { Literal };
+        (file) => { Verbatim };
         (event_date) => { Date };
         (event_title) => { Literal };
         (event_title_addon) => { Literal };
-        (how_published) => { Literal };
+        (how_published) => { Note };
         (institution) => { List<Literal> };
         (isbn) => { Literal }; 
         (issn) => { Literal }; 
         (issue) => { Literal }; 
         (issue_subtitle) => { Literal }; 
         (issue_title) => { Literal }; 
+        (keywords) => { Literal };
+        (langid) => { Literal };
+        (license) => { Literal };
         (location) => { List<Literal> };
         (journal_subtitle) => { Literal }; 
         (journal_title) => { Literal };
         (main_title) => { Literal };
         (main_title_addon) => { Literal };
         (main_subtitle) => { Literal };
-        (month) => { Int };
-        (note) => { Literal };        
+        (month) => { Month };
+        (note) => { Note };
         (number) => { Literal };
         (organization) => { List<Literal> };
+        (orig_date) => { Date };
+        (orig_title) => { Literal };
         (pages) => { Range };
         (page_total) => { Literal };
         (part) => { Literal }; 
         (pubstate) => { PubState };
         (publisher) => { List<Literal> };
+        (repository) => { Uri };
         (series) => { Literal };
+        (short_journal) => { Literal };
         (subtitle) => { Literal };
         (title) => { Literal };
         (title_addon) => { Literal };
@@ -287,11 +896,12 @@ pub mod field {
         (version) => { Literal };
         (volume) => { Literal };
         (volumes) => { Literal };
-        (year) => { Int };
+        (year) => { Year };
     }
 
     #[rustfmt::skip]
     macro_rules! field_id {
+        (abstract_) => { "abstract" };
         (book_title) => { "booktitle" };
         (book_title_addon) => { "booktitleaddon" };
         (book_subtitle) => { "booksubtitle" };
@@ -308,7 +918,10 @@ pub mod field {
         (main_title) => { "maintitle" };
         (main_title_addon) => { "maintitleaddon" };
         (main_subtitle) => { "mainsubtitle" };
+        (orig_date) => { "origdate" };
+        (orig_title) => { "origtitle" };
         (page_total) => { "pagetotal" };
+        (short_journal) => { "shortjournal" };
         (title_addon) => { "titleaddon" };
         (type_) => { "type" };
         (url_date) => { "urldate" };
@@ -335,9 +948,83 @@ pub mod field {
 }
 
 pub mod entry {
+    macro_rules! write_field {
+        ($f:expr, $on_unknown:expr, $name:expr, $val:expr) => {
+            if $val.is_empty() {
+                Ok(())
+            } else if $val.is_macro() {
+                writeln!(
+                    $f,
+                    "    {} = {},",
+                    $name,
+                    $val.biblatex().on_unknown($on_unknown)
+                )
+            } else {
+                writeln!(
+                    $f,
+                    "    {} = {{{}}},",
+                    $name,
+                    $val.biblatex().on_unknown($on_unknown)
+                )
+            }
+        };
+    }
+
+    /// The names fields are written with
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum FieldNames {
+        /// BibLaTeX's field names
+        #[default]
+        Biblatex,
+        /// The classic BibTeX names for the fields which BibLaTeX renamed, for styles which
+        /// expect them: `journal`, `address`, `school` for theses, and `year` instead of `date`.
+        Bibtex,
+    }
+
+    impl FieldNames {
+        /// The name to write the field `name` of a `@entry_type` entry with
+        pub fn rename<'a>(self, entry_type: &str, name: &'a str) -> &'a str {
+            match (self, entry_type, name) {
+                (FieldNames::Biblatex, _, name) => name,
+                (_, _, "journaltitle") => "journal",
+                (_, _, "location") => "address",
+                (_, _, "date") => "year",
+                (_, "thesis", "institution") => "school",
+                (_, _, name) => name,
+            }
+        }
+
+        /// Like [`rename`](Self::rename), but `None` if the field is renamed to one which was
+        /// already written, e.g. `date` to `year`.
+        fn rename_unwritten<'a>(
+            self,
+            written: &mut Vec<&'a str>,
+            entry_type: &str,
+            name: &'a str,
+        ) -> Option<&'a str> {
+            let renamed = self.rename(entry_type, name);
+            if renamed != name && written.contains(&renamed) {
+                return None;
+            }
+            written.push(renamed);
+            Some(renamed)
+        }
+    }
+
+    /// An entry or bibliography written with the given [`FieldNames`] and policy for unknown
+    /// characters.  Writing with [`FieldNames::Biblatex`] is the same as [`ToBiblatex::biblatex`].
+    pub struct WithFieldNames<'a, T: ?Sized>(pub &'a T, pub FieldNames, pub UnknownCharPolicy);
+
+    impl<'a, T: ?Sized> WithFieldNames<'a, T> {
+        /// Set the policy for characters which have no known LaTeX equivalent.
+        pub fn on_unknown(self, policy: UnknownCharPolicy) -> Self {
+            WithFieldNames(self.0, self.1, policy)
+        }
+    }
+
     macro_rules! entry_struct {
         (
-            $tyname:ident $biber_name:literal ;
+            $tyname:ident $builder:ident $biber_name:literal ;
             $($req_field:ident),* $(,)? ;
             $($opt_field:ident),* $(,)?
         ) => {
@@ -354,6 +1041,8 @@ pub mod entry {
                 $(
                     pub $opt_field : Option<field_ty!($opt_field)>,
                 )*
+                /// Fields without a typed counterpart, written after all the typed fields.
+                pub extra_fields: BTreeMap<String, Literal>,
             }
 
             impl_tobiblatex!{$tyname}
@@ -368,24 +1057,171 @@ pub mod entry {
                     Self {
                         id,
                         $($req_field,)*
-                        $($opt_field: None),*
+                        $($opt_field: None,)*
+                        extra_fields: BTreeMap::new(),
+                    }
+                }
+
+                /// The ways the entry breaks BibLaTeX's constraints, see [`Entry::validate`].
+                fn problems(&self, on_unknown: UnknownCharPolicy) -> Vec<String> {
+                    use std::fmt::Write;
+                    let mut problems = Vec::new();
+                    let has_field = |name: &str| false $(
+                        || (field_id!($opt_field) == name
+                            && self.$opt_field.as_ref().map_or(false, |v| !v.is_empty()))
+                    )*;
+                    // BibLaTeX accepts an `editor` in place of the `author`
+                    let has_editor = has_field("editor");
+                    // Required fields may be inherited from the `crossref` parent
+                    let has_crossref = has_field("crossref");
+                    $(
+                        let val = &self.$req_field;
+                        let mut written = String::new();
+                        if write!(written, "{}", val.biblatex().on_unknown(on_unknown)).is_err() {
+                            problems.push(format!("`{}` can't be written in LaTeX", field_id!($req_field)));
+                        } else if val.is_empty() || written.trim().is_empty() {
+                            if !(has_crossref || field_id!($req_field) == "author" && has_editor) {
+                                problems.push(format!("required field `{}` is empty", field_id!($req_field)));
+                            }
+                        }
+                        if let Some(v) = val.violation() {
+                            problems.push(format!("`{}`: {}", field_id!($req_field), v));
+                        }
+                    )*
+                    $(
+                        if let Some(val) = self.$opt_field.as_ref() {
+                            if write!(String::new(), "{}", val.biblatex().on_unknown(on_unknown)).is_err() {
+                                problems.push(format!("`{}` can't be written in LaTeX", field_id!($opt_field)));
+                            }
+                            if let Some(v) = val.violation() {
+                                problems.push(format!("`{}`: {}", field_id!($opt_field), v));
+                            }
+                        }
+                    )*
+                    for (name, val) in &self.extra_fields {
+                        if write!(String::new(), "{}", val.biblatex().on_unknown(on_unknown)).is_err() {
+                            problems.push(format!("`{}` can't be written in LaTeX", name));
+                        }
+                    }
+                    problems
+                }
+
+                /// The fields which are probably mistakes, see [`Entry::warnings`].
+                fn warnings(&self) -> Vec<String> {
+                    let mut warnings = Vec::new();
+                    $(
+                        if let Some(w) = self.$req_field.warning() {
+                            warnings.push(format!("`{}`: {}", field_id!($req_field), w));
+                        }
+                    )*
+                    $(
+                        if let Some(w) = self.$opt_field.as_ref().and_then(ToBiblatex::warning) {
+                            warnings.push(format!("`{}`: {}", field_id!($opt_field), w));
+                        }
+                    )*
+                    warnings
+                }
+
+                /// Start building an entry with named setters.
+                pub fn builder(id: impl Into<String>) -> $builder {
+                    $builder {
+                        id: id.into(),
+                        $($req_field: None,)*
+                        $($opt_field: None,)*
+                        extra_fields: BTreeMap::new(),
                     }
                 }
             }
 
+            #[doc = concat!("Builder for [`", stringify!($tyname), "`] entries, created with [`", stringify!($tyname), "::builder`].")]
+            ///
+            /// Required fields are checked when [`build`](Self::build) is called.
+            #[derive(Debug, Clone)]
+            pub struct $builder {
+                id: String,
+                $(
+                    $req_field : Option<field_ty!($req_field)>,
+                )*
+                $(
+                    $opt_field : Option<field_ty!($opt_field)>,
+                )*
+                extra_fields: BTreeMap<String, Literal>,
+            }
+
+            impl $builder {
+                $(
+                    pub fn $req_field(mut self, val: impl Into<field_ty!($req_field)>) -> Self {
+                        self.$req_field = Some(val.into());
+                        self
+                    }
+                )*
+                $(
+                    pub fn $opt_field(mut self, val: impl Into<field_ty!($opt_field)>) -> Self {
+                        self.$opt_field = Some(val.into());
+                        self
+                    }
+                )*
+
+                /// Add a field without a typed counterpart.
+                pub fn extra_field(mut self, name: impl Into<String>, val: impl Into<Literal>) -> Self {
+                    self.extra_fields.insert(name.into(), val.into());
+                    self
+                }
+
+                /// Finish the entry, failing with the names of all required fields which were not set.
+                pub fn build(self) -> Result<$tyname> {
+                    let mut missing: Vec<&str> = Vec::new();
+                    $(
+                        if self.$req_field.is_none() {
+                            missing.push(field_id!($req_field));
+                        }
+                    )*
+                    if !missing.is_empty() {
+                        return Err(anyhow!(
+                            "@{} entry `{}` is missing required field(s): {}",
+                            $biber_name,
+                            self.id,
+                            missing.join(", ")
+                        ));
+                    }
+                    Ok($tyname {
+                        id: self.id,
+                        $($req_field: self.$req_field.unwrap(),)*
+                        $($opt_field: self.$opt_field,)*
+                        extra_fields: self.extra_fields,
+                    })
+                }
+            }
+
             impl<'a> Display for FmtBiblatex<'a, $tyname> {
                 fn fmt(&self, f: &mut Formatter) -> FmtResult {
-                    let e = self.0;
+                    WithFieldNames(self.0, FieldNames::Biblatex, self.1).fmt(f)
+                }
+            }
+
+            impl<'a> Display for WithFieldNames<'a, $tyname> {
+                fn fmt(&self, f: &mut Formatter) -> FmtResult {
+                    let WithFieldNames(e, names, on_unknown) = *self;
+                    let mut written = Vec::new();
                     writeln!(f, "@{}{{{},", $biber_name, &e.id)?;
 
                     $(
-                        writeln!(f, "    {} = {{{}}},", field_id!($req_field), e.$req_field.biblatex())?;
+                        if let Some(name) = names.rename_unwritten(&mut written, $biber_name, field_id!($req_field)) {
+                            write_field!(f, on_unknown, name, e.$req_field)?;
+                        }
                     )*
                     $(
                         if let Some(val) = e.$opt_field.as_ref() {
-                            writeln!(f, "    {} = {{{}}},", field_id!($opt_field), val.biblatex())?;
+                            if let Some(name) = names.rename_unwritten(&mut written, $biber_name, field_id!($opt_field)) {
+                                write_field!(f, on_unknown, name, val)?;
+                            }
                         }
                     )*
+                    for (name, val) in &e.extra_fields {
+                        if let Some(name) = names.rename_unwritten(&mut written, $biber_name, name) {
+                            write_field!(f, on_unknown, name, val)?;
+                        }
+                    }
                     f.write_str("}\n")
                 }
             }
@@ -395,6 +1231,7 @@ pub mod entry {
 
     use super::types::*;
     use super::*;
+    use std::collections::BTreeMap;
 
     #[derive(Clone, Debug)]
     #[non_exhaustive]
@@ -402,9 +1239,13 @@ pub mod entry {
         Article(Article),
         Thesis(Thesis),
         InProceedings(InProceedings),
+        Proceedings(Proceedings),
         Report(Report),
         Misc(Misc),
         Book(Book),
+        Online(Online),
+        Software(Software),
+        Dataset(Dataset),
     }
 
     impl_tobiblatex! {Entry}
@@ -415,36 +1256,223 @@ pub mod entry {
                 Entry::Article(e) => &e.id,
                 Entry::Thesis(e) => &e.id,
                 Entry::InProceedings(e) => &e.id,
+                Entry::Proceedings(e) => &e.id,
                 Entry::Report(e) => &e.id,
                 Entry::Misc(e) => &e.id,
                 Entry::Book(e) => &e.id,
+                Entry::Online(e) => &e.id,
+                Entry::Software(e) => &e.id,
+                Entry::Dataset(e) => &e.id,
+            }
+        }
+
+        pub fn extra_fields_mut(&mut self) -> &mut BTreeMap<String, Literal> {
+            match self {
+                Entry::Article(e) => &mut e.extra_fields,
+                Entry::Thesis(e) => &mut e.extra_fields,
+                Entry::InProceedings(e) => &mut e.extra_fields,
+                Entry::Proceedings(e) => &mut e.extra_fields,
+                Entry::Report(e) => &mut e.extra_fields,
+                Entry::Misc(e) => &mut e.extra_fields,
+                Entry::Book(e) => &mut e.extra_fields,
+                Entry::Online(e) => &mut e.extra_fields,
+                Entry::Software(e) => &mut e.extra_fields,
+                Entry::Dataset(e) => &mut e.extra_fields,
+            }
+        }
+
+        /// Write the entry with `names` instead of BibLaTeX's field names
+        pub fn with_field_names(&self, names: FieldNames) -> WithFieldNames<'_, Self> {
+            WithFieldNames(self, names, UnknownCharPolicy::default())
+        }
+
+        /// Check the constraints BibLaTeX places on the entry beyond its required fields being
+        /// set: the key must be usable by biber, required fields must not be empty, values such as
+        /// months must make sense, and every field must be writable with the `on_unknown` policy.
+        /// Every problem is listed in the error.  Values which BibLaTeX accepts but are probably
+        /// mistakes, such as backwards page ranges, are listed by [`Entry::warnings`] instead.
+        pub fn validate(&self, on_unknown: UnknownCharPolicy) -> Result<()> {
+            let mut problems = match self {
+                Entry::Article(e) => e.problems(on_unknown),
+                Entry::Thesis(e) => e.problems(on_unknown),
+                Entry::InProceedings(e) => e.problems(on_unknown),
+                Entry::Proceedings(e) => e.problems(on_unknown),
+                Entry::Report(e) => e.problems(on_unknown),
+                Entry::Misc(e) => e.problems(on_unknown),
+                Entry::Book(e) => e.problems(on_unknown),
+                Entry::Online(e) => e.problems(on_unknown),
+                Entry::Software(e) => e.problems(on_unknown),
+                Entry::Dataset(e) => e.problems(on_unknown),
+            };
+            let id = self.id();
+            if id.is_empty() {
+                problems.insert(0, "the key is empty".to_string());
+            } else if let Some(c) = id
+                .chars()
+                .find(|&c| c.is_whitespace() || r#",{}%#"\="#.contains(c))
+            {
+                problems.insert(0, format!("the key contains {:?}", c));
+            }
+            if problems.is_empty() {
+                return Ok(());
+            }
+            Err(anyhow!(
+                "entry `{}` is invalid: {}",
+                id,
+                problems.join("; ")
+            ))
+        }
+
+        /// The values which BibLaTeX accepts but are probably mistakes, such as a page range which
+        /// ends before it starts.
+        pub fn warnings(&self) -> Vec<String> {
+            match self {
+                Entry::Article(e) => e.warnings(),
+                Entry::Thesis(e) => e.warnings(),
+                Entry::InProceedings(e) => e.warnings(),
+                Entry::Proceedings(e) => e.warnings(),
+                Entry::Report(e) => e.warnings(),
+                Entry::Misc(e) => e.warnings(),
+                Entry::Book(e) => e.warnings(),
+                Entry::Online(e) => e.warnings(),
+                Entry::Software(e) => e.warnings(),
+                Entry::Dataset(e) => e.warnings(),
+            }
+        }
+
+        /// The `file` field, which every entry type has
+        pub fn file_mut(&mut self) -> &mut Option<Verbatim> {
+            match self {
+                Entry::Article(e) => &mut e.file,
+                Entry::Thesis(e) => &mut e.file,
+                Entry::InProceedings(e) => &mut e.file,
+                Entry::Proceedings(e) => &mut e.file,
+                Entry::Report(e) => &mut e.file,
+                Entry::Misc(e) => &mut e.file,
+                Entry::Book(e) => &mut e.file,
+                Entry::Online(e) => &mut e.file,
+                Entry::Software(e) => &mut e.file,
+                Entry::Dataset(e) => &mut e.file,
             }
         }
     }
 
     impl<'a> Display for FmtBiblatex<'a, Entry> {
         fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-            match &self.0 {
-                Entry::Article(e) => e.biblatex().fmt(f),
-                Entry::Thesis(e) => e.biblatex().fmt(f),
-                Entry::InProceedings(e) => e.biblatex().fmt(f),
-                Entry::Report(e) => e.biblatex().fmt(f),
-                Entry::Misc(e) => e.biblatex().fmt(f),
-                Entry::Book(e) => e.biblatex().fmt(f),
+            self.0
+                .with_field_names(FieldNames::Biblatex)
+                .on_unknown(self.1)
+                .fmt(f)
+        }
+    }
+
+    impl<'a> Display for WithFieldNames<'a, Entry> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+            let WithFieldNames(e, names, on_unknown) = *self;
+            match e {
+                Entry::Article(e) => WithFieldNames(e, names, on_unknown).fmt(f),
+                Entry::Thesis(e) => WithFieldNames(e, names, on_unknown).fmt(f),
+                Entry::InProceedings(e) => WithFieldNames(e, names, on_unknown).fmt(f),
+                Entry::Proceedings(e) => WithFieldNames(e, names, on_unknown).fmt(f),
+                Entry::Report(e) => WithFieldNames(e, names, on_unknown).fmt(f),
+                Entry::Misc(e) => WithFieldNames(e, names, on_unknown).fmt(f),
+                Entry::Book(e) => WithFieldNames(e, names, on_unknown).fmt(f),
+                Entry::Online(e) => WithFieldNames(e, names, on_unknown).fmt(f),
+                Entry::Software(e) => WithFieldNames(e, names, on_unknown).fmt(f),
+                Entry::Dataset(e) => WithFieldNames(e, names, on_unknown).fmt(f),
+            }
+        }
+    }
+
+    /// A list of entries, written one after the other with a blank line between each.
+    #[derive(Clone, Debug, Default)]
+    pub struct Bibliography(pub Vec<Entry>);
+
+    impl_tobiblatex! {Bibliography}
+
+    impl Bibliography {
+        /// Write the entries with `names` instead of BibLaTeX's field names
+        pub fn with_field_names(&self, names: FieldNames) -> WithFieldNames<'_, Self> {
+            WithFieldNames(self, names, UnknownCharPolicy::default())
+        }
+
+        /// Sort the entries by key.
+        pub fn sort(&mut self) {
+            self.0.sort_by(|a, b| a.id().cmp(b.id()));
+        }
+
+        /// Remove entries whose key is used by an earlier entry, returning the removed keys.
+        pub fn dedup(&mut self) -> Vec<String> {
+            let mut seen = std::collections::HashSet::new();
+            let mut removed = Vec::new();
+            self.0.retain(|e| {
+                if seen.insert(e.id().to_string()) {
+                    true
+                } else {
+                    removed.push(e.id().to_string());
+                    false
+                }
+            });
+            removed
+        }
+    }
+
+    lazy_static::lazy_static! {
+        static ref ENTRY_START: regex::Regex =
+            regex::Regex::new(r"(?m)^\s*@\s*([A-Za-z]+)\s*[{(]\s*([^,\s{}()]+)\s*,").unwrap();
+    }
+
+    /// The keys of the entries in the BibLaTeX source `bib`, found by looking for `@type{key,` at
+    /// the start of each line.  `@string`, `@preamble` and `@comment` are skipped.  This is a
+    /// quick scan, not a parser: entries commented out with `%` are still found.
+    pub fn entry_keys(bib: &str) -> Vec<&str> {
+        ENTRY_START
+            .captures_iter(bib)
+            .filter(|c| {
+                !["string", "preamble", "comment"]
+                    .iter()
+                    .any(|ty| c[1].eq_ignore_ascii_case(ty))
+            })
+            .map(|c| c.get(2).unwrap().as_str())
+            .collect()
+    }
+
+    impl From<Vec<Entry>> for Bibliography {
+        fn from(entries: Vec<Entry>) -> Self {
+            Bibliography(entries)
+        }
+    }
+
+    impl<'a> Display for FmtBiblatex<'a, Bibliography> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+            self.0
+                .with_field_names(FieldNames::Biblatex)
+                .on_unknown(self.1)
+                .fmt(f)
+        }
+    }
+
+    impl<'a> Display for WithFieldNames<'a, Bibliography> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+            for e in &self.0 .0 {
+                writeln!(f, "{}", e.with_field_names(self.1).on_unknown(self.2))?;
             }
+            Ok(())
         }
     }
 
     entry_struct! {
-        Article "article";
+        Article ArticleBuilder "article";
         author,
         title,
         journal_title,
         year,
         ;
+        abstract_,
         addendum,
         annotator,
         commentator,
+        date,
         doi,
         editor,
         // editora,
@@ -454,19 +1482,25 @@ pub mod entry {
         eprint,
         eprint_class,
         eprint_type,
+        file,
         issn,
         issue,
         issue_subtitle,
         issue_title,
         journal_subtitle,
-        // language
+        keywords,
+        langid,
+        // language,
         month,
         note,
         number,
+        orig_date,
+        orig_title,
         // origlanguage,
         pages,
         pubstate,
         series,
+        short_journal,
         subtitle,
         title_addon,
         translator,
@@ -477,24 +1511,31 @@ pub mod entry {
     }
 
     entry_struct! {
-        Thesis "thesis";
+        Thesis ThesisBuilder "thesis";
         author,
         title,
         type_,
         institution,
         year,
         ;
+        abstract_,
         addendum,
         chapter,
+        date,
         doi,
         eprint_class,
         eprint_type,
         eprint,
+        file,
         isbn,
+        keywords,
+        langid,
         // language,
         location,
         month,
         note,
+        orig_date,
+        orig_title,
         page_total,
         pages,
         pubstate,
@@ -505,16 +1546,19 @@ pub mod entry {
     }
 
     entry_struct! {
-        InProceedings "inproceedings";
+        InProceedings InProceedingsBuilder "inproceedings";
         author,
         title,
         book_title,
         year,
         ;
+        abstract_,
         addendum,
         book_subtitle,
         book_title_addon,
         chapter,
+        crossref,
+        date,
         doi,
         editor,
         eprint,
@@ -523,7 +1567,10 @@ pub mod entry {
         event_date,
         event_title,
         event_title_addon,
+        file,
         isbn,
+        keywords,
+        langid,
         // language,
         location,
         main_subtitle,
@@ -533,6 +1580,8 @@ pub mod entry {
         note,
         number,
         organization,
+        orig_date,
+        orig_title,
         pages,
         part,
         publisher,
@@ -548,25 +1597,73 @@ pub mod entry {
     }
 
     entry_struct! {
-        Report "report";
+        Proceedings ProceedingsBuilder "proceedings";
+        title,
+        year,
+        ;
+        addendum,
+        date,
+        doi,
+        editor,
+        eprint,
+        eprint_class,
+        eprint_type,
+        event_date,
+        event_title,
+        event_title_addon,
+        file,
+        isbn,
+        keywords,
+        langid,
+        // language,
+        location,
+        main_subtitle,
+        main_title,
+        main_title_addon,
+        month,
+        note,
+        number,
+        organization,
+        part,
+        publisher,
+        pubstate,
+        series,
+        subtitle,
+        title_addon,
+        url,
+        url_date,
+        venue,
+        volume,
+        volumes,
+    }
+
+    entry_struct! {
+        Report ReportBuilder "report";
         author,
         title,
         type_,
         institution,
         year,
         ;
+        abstract_,
         addendum,
         chapter,
+        date,
         doi,
         eprint,
         eprint_class,
         eprint_type,
+        file,
         // isrn,
+        keywords,
+        langid,
         // language,
         location,
         month,
         note,
         number,
+        orig_date,
+        orig_title,
         pages,
         page_total,
         pubstate,
@@ -578,19 +1675,24 @@ pub mod entry {
     }
 
     entry_struct! {
-        Misc "misc";
+        Misc MiscBuilder "misc";
         author,
         title,
         year,
         ;
+        abstract_,
         addendum,
         chapter,
+        date,
         doi,
         edition,
         eprint,
         eprint_class,
         eprint_type,
+        file,
         isbn,
+        keywords,
+        langid,
         // language,
         location,
         note,
@@ -610,16 +1712,18 @@ pub mod entry {
     }
 
     entry_struct! {
-        Book "book";
+        Book BookBuilder "book";
         author,
         title,
         year,
         ;
+        abstract_,
         addendum,
         // afterword,
         annotator,
         chapter,
         commentator,
+        date,
         doi,
         edition,
         editor,
@@ -629,9 +1733,12 @@ pub mod entry {
         // eprint,
         // eprintclass,
         // eprinttype,
+        file,
         // foreword,
         // introduction,
         isbn,
+        keywords,
+        langid,
         // language,
         location,
         // mainsubtitle,
@@ -639,6 +1746,8 @@ pub mod entry {
         // maintitleaddon,
         note,
         number,
+        orig_date,
+        orig_title,
         // origlanguage,
         pages,
         // pagetotal,
@@ -654,4 +1763,402 @@ pub mod entry {
         volume,
         volumes,
     }
+
+    entry_struct! {
+        Online OnlineBuilder "online";
+        author,
+        title,
+        year,
+        url,
+        ;
+        abstract_,
+        addendum,
+        date,
+        doi,
+        eprint,
+        eprint_class,
+        eprint_type,
+        file,
+        keywords,
+        langid,
+        // language,
+        month,
+        note,
+        organization,
+        pubstate,
+        subtitle,
+        title_addon,
+        url_date,
+        version,
+    }
+
+    // `@software` comes from the biblatex-software package, and is an alias for `@misc` without it
+    entry_struct! {
+        Software SoftwareBuilder "software";
+        author,
+        title,
+        year,
+        ;
+        abstract_,
+        addendum,
+        date,
+        doi,
+        file,
+        how_published,
+        keywords,
+        langid,
+        license,
+        month,
+        note,
+        organization,
+        publisher,
+        repository,
+        subtitle,
+        title_addon,
+        url,
+        url_date,
+        version,
+    }
+
+    entry_struct! {
+        Dataset DatasetBuilder "dataset";
+        author,
+        title,
+        year,
+        ;
+        abstract_,
+        addendum,
+        date,
+        doi,
+        edition,
+        editor,
+        eprint,
+        eprint_class,
+        eprint_type,
+        file,
+        how_published,
+        keywords,
+        langid,
+        // language,
+        license,
+        location,
+        month,
+        note,
+        number,
+        organization,
+        publisher,
+        pubstate,
+        series,
+        subtitle,
+        title_addon,
+        type_,
+        url,
+        url_date,
+        version,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::entry::*;
+    use super::types::*;
+    use super::*;
+
+    #[test]
+    fn note_urls() {
+        let fmt = |s: &str| Note::from(s).biblatex().to_string();
+        assert_eq!(fmt("100% of 5_000"), r"100\% of 5\_000");
+        assert_eq!(
+            fmt("Available at https://example.com/a_b?x=1&y=~2."),
+            r"Available at \url{https://example.com/a_b?x=1&y=~2}."
+        );
+        assert_eq!(
+            fmt("(see http://example.com/100%25#top)"),
+            r"(see \url{http://example.com/100\%25\#top})"
+        );
+        assert_eq!(
+            Literal::from("https://example.com/a_b")
+                .biblatex()
+                .to_string(),
+            r"https://example.com/a\_b"
+        );
+    }
+
+    #[test]
+    fn literal_formatting() {
+        let fmt = |s: &str| Literal::from(s).biblatex().to_string();
+        assert_eq!(
+            fmt("Na<sub>2</sub>CO<sub>3</sub> <i>in vivo</i>"),
+            r"Na\textsubscript{2}CO\textsubscript{3} \textit{in vivo}"
+        );
+        assert_eq!(
+            fmt("<b>x<sup>2</sup></b>"),
+            r"\textbf{x\textsuperscript{2}}"
+        );
+        assert_eq!(
+            Note::from("<i>see https://example.com</i>")
+                .biblatex()
+                .to_string(),
+            r"\textit{see \url{https://example.com}}"
+        );
+        assert_eq!(fmt("<i>unclosed"), "<i>unclosed");
+        assert_eq!(
+            fmt(&crate::strip_markup(
+                "&lt;i&gt;x&lt;/i&gt; <italic>y</italic>"
+            )),
+            r"<i>x</i> \textit{y}"
+        );
+        assert_eq!(fmt("<i>crossed<b></i></b>"), "<i>crossed<b></i></b>");
+    }
+
+    #[test]
+    fn date() {
+        assert_eq!(Date::year(2019).biblatex().to_string(), "2019");
+        assert_eq!(Date::year_month(2019, 6).biblatex().to_string(), "2019-06");
+        assert_eq!(Date::full(2019, 6, 1).biblatex().to_string(), "2019-06-01");
+        assert_eq!(
+            Date::full(2019, 12, 31).biblatex().to_string(),
+            "2019-12-31"
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let e = Article::builder("smith2020")
+            .author(vec![Name::new("Jane".into(), "Smith".into())])
+            .title("A title")
+            .journal_title("Some Journal")
+            .year(2020)
+            .volume("3")
+            .build()
+            .unwrap();
+        assert_eq!(e.id, "smith2020");
+        assert_eq!(e.year, Year::Number(2020));
+        assert!(e.volume.is_some());
+        assert!(e.pages.is_none());
+
+        let err = Article::builder("smith2020")
+            .title("A title")
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "@article entry `smith2020` is missing required field(s): author, journaltitle, year"
+        );
+    }
+
+    #[test]
+    fn software() {
+        let e = Software::builder("tex-tools")
+            .author(vec![Name::new("Jane".into(), "Smith".into())])
+            .title("tex_tools")
+            .year(2022)
+            .version("0.1.0")
+            .license("MIT OR Apache-2.0")
+            .repository("https://github.com/ykrist/tex-tools/tree/main#readme_%20")
+            .build()
+            .unwrap();
+        assert_eq!(
+            e.biblatex().to_string(),
+            "@software{tex-tools,\n    author = {Smith, Jane},\n    title = {tex\\_tools},\n    \
+             year = {2022},\n    license = {MIT OR Apache-2.0},\n    \
+             repository = {https://github.com/ykrist/tex-tools/tree/main#readme_%20},\n    \
+             version = {0.1.0},\n}\n"
+        );
+    }
+
+    #[test]
+    fn bibtex_field_names() {
+        let e = Entry::Thesis(
+            Thesis::builder("smith2020")
+                .author(vec![Name::new("Jane".into(), "Smith".into())])
+                .title("A thesis")
+                .type_("phdthesis")
+                .institution(vec![Literal::from("University of Auckland")])
+                .year(2020)
+                .location(vec![Literal::from("Auckland")])
+                .date(Date::year_month(2020, 22))
+                .build()
+                .unwrap(),
+        );
+        assert_eq!(
+            e.with_field_names(FieldNames::Bibtex).to_string(),
+            "@thesis{smith2020,\n    author = {Smith, Jane},\n    title = {A thesis},\n    \
+             type = {phdthesis},\n    school = {University of Auckland},\n    year = {2020},\n    \
+             address = {Auckland},\n}\n"
+        );
+        assert_eq!(
+            e.with_field_names(FieldNames::Biblatex).to_string(),
+            e.biblatex().to_string()
+        );
+        assert_eq!(
+            FieldNames::Bibtex.rename("article", "journaltitle"),
+            "journal"
+        );
+        assert_eq!(
+            FieldNames::Bibtex.rename("report", "institution"),
+            "institution"
+        );
+    }
+
+    #[test]
+    fn validate() {
+        let article = |id: &str, title: &str| {
+            Article::builder(id)
+                .author(vec![Name::new("Jane".into(), "Smith".into())])
+                .title(title)
+                .journal_title("Some Journal")
+                .year(2020)
+        };
+        let valid = Entry::Article(article("smith2020", "A title").build().unwrap());
+        assert!(valid.validate(UnknownCharPolicy::default()).is_ok());
+
+        let invalid = Entry::Article(
+            article("smith 2020", " ")
+                .month(13)
+                .pages(Range::List(vec![
+                    Range::Single(1),
+                    Range::Multi {
+                        start: 20,
+                        end: Some(10),
+                    },
+                ]))
+                .build()
+                .unwrap(),
+        );
+        assert_eq!(
+            invalid.validate(UnknownCharPolicy::default()).unwrap_err().to_string(),
+            "entry `smith 2020` is invalid: the key contains ' '; required field `title` is empty; \
+             `month`: month 13 is not between 1 and 12"
+        );
+        assert_eq!(
+            invalid.warnings(),
+            ["`pages`: range 20-10 ends before it starts"]
+        );
+        assert!(valid.warnings().is_empty());
+
+        let edited = |editor: Vec<Name>| {
+            let e = article("smith2020", "A title")
+                .author(vec![])
+                .editor(editor);
+            Entry::Article(e.build().unwrap())
+        };
+        assert!(edited(vec![Name::new("David".into(), "Hilbert".into())])
+            .validate(UnknownCharPolicy::default())
+            .is_ok());
+        assert_eq!(
+            edited(vec![])
+                .validate(UnknownCharPolicy::default())
+                .unwrap_err()
+                .to_string(),
+            "entry `smith2020` is invalid: required field `author` is empty"
+        );
+
+        let unescapable = Entry::Article(article("smith2020", "漢字").build().unwrap());
+        assert_eq!(
+            unescapable
+                .validate(UnknownCharPolicy::default())
+                .unwrap_err()
+                .to_string(),
+            "entry `smith2020` is invalid: `title` can't be written in LaTeX"
+        );
+        assert!(unescapable.validate(UnknownCharPolicy::Passthrough).is_ok());
+        assert!(unescapable
+            .biblatex()
+            .on_unknown(UnknownCharPolicy::Passthrough)
+            .to_string()
+            .contains("title = {漢字},"));
+    }
+
+    #[test]
+    fn bibliography() {
+        let article = |id: &str| {
+            Entry::Article(
+                Article::builder(id)
+                    .author(vec![Name::new("Jane".into(), "Smith".into())])
+                    .title("A title")
+                    .journal_title("Some Journal")
+                    .year(2020)
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let mut bib = Bibliography(vec![article("b"), article("a"), article("b")]);
+        assert_eq!(bib.dedup(), ["b"]);
+        bib.sort();
+        let output = bib.biblatex().to_string();
+        assert!(output.starts_with("@article{a,\n"));
+        assert!(output.contains("}\n\n@article{b,\n"));
+        assert!(output.ends_with("}\n\n"));
+    }
+
+    #[test]
+    fn keys() {
+        let bib = "@string{acm = {ACM}}\n\n@Article{smith2020,\n    title = {A},\n}\n\n\
+                   @comment{x, y}\n  @book( jones:b ,\n}\n";
+        assert_eq!(entry_keys(bib), ["smith2020", "jones:b"]);
+    }
+
+    #[test]
+    fn parse_name() {
+        let fmt = |s: &str| Name::parse(s).biblatex().to_string();
+        assert_eq!(fmt("Jane Q. Smith"), "Smith, Jane Q.");
+        assert_eq!(fmt("Smith, Jane Q."), "Smith, Jane Q.");
+        assert_eq!(fmt("Ludwig van Beethoven"), "van Beethoven, Ludwig");
+        assert_eq!(fmt("van Beethoven, Ludwig"), "van Beethoven, Ludwig");
+        assert_eq!(
+            fmt("Charles Louis de la Vallée Poussin"),
+            r"de la Vallée Poussin, Charles Louis".replace('é', r"\'{e}")
+        );
+        assert_eq!(fmt("Kelley, Jr, James E."), "Kelley, Jr, James E.");
+        assert_eq!(fmt("James E. Kelley Jr."), "Kelley, Jr., James E.");
+        assert_eq!(
+            fmt("{World Health Organization}"),
+            "{World Health Organization}"
+        );
+        assert_eq!(fmt("Plato"), "{Plato}");
+
+        let mao = Name::new("Zedong".into(), "Mao".into()).with_family_first();
+        assert_eq!(mao.biblatex().to_string(), "{Mao Zedong}");
+
+        let list = Name::parse_list("Jane Q. Smith and Doe, John and {Barnes and Noble}");
+        assert_eq!(
+            list.biblatex().to_string(),
+            "Smith, Jane Q. and Doe, John and {Barnes and Noble}"
+        );
+    }
+
+    #[test]
+    fn name_list_others() {
+        let mut list = Name::parse_list("Ada Lovelace and Charles Babbage and others");
+        assert!(list.0[2].is_others());
+        assert_eq!(
+            list.biblatex().to_string(),
+            "Lovelace, Ada and Babbage, Charles and others"
+        );
+        list.truncate_with_others(1);
+        assert_eq!(list.biblatex().to_string(), "Lovelace, Ada and others");
+
+        let mut list = Name::parse_list("Ada Lovelace and others");
+        list.truncate_with_others(2);
+        assert_eq!(list.biblatex().to_string(), "Lovelace, Ada and others");
+
+        let mut list = Name::parse_list("Ada Lovelace and Charles Babbage");
+        list.truncate_with_others(2);
+        assert_eq!(list.0.len(), 2);
+    }
+
+    #[test]
+    fn name_roundtrip() {
+        for n in [
+            Name::new("John Michael".into(), "Smith".into()),
+            Name::new("J. M.".into(), "Smith".into()),
+            Name::new("Ludwig".into(), "Beethoven".into()).with_particle("van".into()),
+            Name::new("Charles Louis".into(), "Vallee Poussin".into())
+                .with_particle("de la".into()),
+            Name::new("Martin Luther".into(), "King".into()).with_suffix("Jr.".into()),
+        ] {
+            assert_eq!(Name::parse(&n.biblatex().to_string()), n);
+        }
+    }
 }
@@ -0,0 +1,185 @@
+//! Check the citations in LaTeX sources against the keys the database converts to.
+use std::collections::HashSet;
+
+use super::*;
+use regex::Regex;
+
+#[derive(Args)]
+pub struct ClArgs {
+    /// LaTeX sources to scan for `\cite`, `\parencite` and `\textcite` commands
+    #[clap(required = true)]
+    tex: Vec<PathBuf>,
+
+    /// The database (CSL JSON format, or YAML if the extension is .yaml or .yml), or glob
+    /// patterns such as "refs/*.json".  Entries are completed from the fetch cache, but nothing
+    /// is fetched.
+    #[clap(short = 'd', long = "db", value_name = "PATH", required = true)]
+    db: Vec<PathBuf>,
+
+    /// Also report database entries which are never cited
+    #[clap(long)]
+    unused: bool,
+
+    /// The field holding each entry's DOI, used to complete entries from the fetch cache.  The
+    /// default also matches `doi`.
+    #[clap(long, value_name = "FIELD", default_value = "DOI")]
+    doi_from: String,
+
+    #[clap(flatten)]
+    convert: convert::ConvertOptions,
+}
+
+/// A key cited in a LaTeX source
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Citation {
+    key: String,
+    /// The line of the citation command, starting from 1
+    line: usize,
+}
+
+/// Remove `%` comments, keeping the line breaks so line numbers are unchanged
+fn strip_comments(tex: &str) -> String {
+    let mut stripped = String::with_capacity(tex.len());
+    for line in tex.split_inclusive('\n') {
+        let mut escaped = false;
+        let end = line
+            .char_indices()
+            .find(|&(_, c)| {
+                let comment = c == '%' && !escaped;
+                escaped = c == '\\' && !escaped;
+                comment
+            })
+            .map(|(i, _)| i);
+        match end {
+            Some(i) => {
+                stripped += &line[..i];
+                if line.ends_with('\n') {
+                    stripped.push('\n');
+                }
+            }
+            None => stripped += line,
+        }
+    }
+    stripped
+}
+
+/// The keys cited by `\cite`, `\parencite` and `\textcite` (and their starred and capitalised
+/// forms) in `tex`, in order of appearance
+fn citations(tex: &str) -> Vec<Citation> {
+    lazy_static! {
+        static ref CITE: Regex = Regex::new(
+            r"\\(?:[Cc]ite|[Pp]arencite|[Tt]extcite)\*?\s*(?:\[[^\]]*\]\s*){0,2}\{([^}]*)\}"
+        )
+        .unwrap();
+    }
+    let tex = strip_comments(tex);
+    let mut citations = Vec::new();
+    for c in CITE.captures_iter(&tex) {
+        let start = c.get(0).unwrap().start();
+        let line = tex[..start].matches('\n').count() + 1;
+        for key in c[1].split(',').map(str::trim).filter(|k| !k.is_empty()) {
+            citations.push(Citation {
+                key: key.to_string(),
+                line,
+            });
+        }
+    }
+    citations
+}
+
+pub fn main(mut args: ClArgs) -> Result<()> {
+    args.convert.load_tables()?;
+    let inputs = validate::expand_inputs(&args.db)?;
+    let mut db: Vec<_> = validate::load_and_validate_db(&inputs, false)?
+        .into_iter()
+        .map(JsonExt::unwrap_object)
+        .collect();
+    fetch::merge_cached(&cache::FetchCache::load()?, &mut db, &args.doi_from);
+    let ids: HashSet<String> = db
+        .iter()
+        .filter_map(|e| e.get(csl_fields::ID)?.as_str())
+        .map(String::from)
+        .collect();
+
+    // Entries which fail to convert (e.g. they haven't been fetched yet) produce no key
+    let bib = fetch::convert_db(db, true, &args.convert)?;
+    let keys: HashSet<_> = bib.0.iter().map(|e| e.id()).collect();
+
+    let mut cited = HashSet::new();
+    let mut count_missing = 0;
+    let mut count_unconverted = 0;
+    for path in &args.tex {
+        let tex = std::fs::read_to_string(path).context_read(path)?;
+        for c in citations(&tex) {
+            if !keys.contains(c.key.as_str()) {
+                if ids.contains(&c.key) {
+                    error!(
+                        file = %path.display(),
+                        line = c.line,
+                        key = %c.key,
+                        "cited entry is in the database but failed to convert"
+                    );
+                    count_unconverted += 1;
+                } else {
+                    error!(
+                        file = %path.display(),
+                        line = c.line,
+                        key = %c.key,
+                        "cited key is not in the database"
+                    );
+                    count_missing += 1;
+                }
+            }
+            cited.insert(c.key);
+        }
+    }
+
+    if args.unused {
+        for e in &bib.0 {
+            if !cited.contains(e.id()) {
+                warn!(id = e.id(), "entry is never cited");
+            }
+        }
+    }
+
+    if count_missing > 0 || count_unconverted > 0 {
+        bail!(
+            "{} citations are not in the database, and {} are of entries which failed to convert",
+            count_missing,
+            count_unconverted
+        );
+    }
+    info!(
+        count_cited = cited.len(),
+        "all citations are in the database"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cite_commands() {
+        let tex = r"As shown by \textcite{knuth1984}, and others \parencite[see][p.~3]{a, b}.
+% \cite{commented}
+50\% of \cite*{c} \citeauthor{d}
+\Textcite
+  {e}";
+        let keys: Vec<_> = citations(tex)
+            .into_iter()
+            .map(|c| (c.key, c.line))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("knuth1984".to_string(), 1),
+                ("a".to_string(), 1),
+                ("b".to_string(), 1),
+                ("c".to_string(), 3),
+                ("e".to_string(), 4),
+            ]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! ANSI highlighting of BibLaTeX written to a terminal, for `fetch --color`.
+use std::io::IsTerminal;
+
+use super::*;
+use regex::Regex;
+
+const ENTRY_TYPE: &str = "\x1b[1;35m";
+const KEY: &str = "\x1b[1;33m";
+const FIELD: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum ColorChoice {
+    /// Highlight if STDOUT is a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether output to STDOUT should be highlighted
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// The change in brace nesting over `line`, ignoring escaped braces
+fn brace_depth_change(line: &str) -> i32 {
+    let mut depth = 0;
+    let mut escaped = false;
+    for c in line.chars() {
+        match c {
+            '{' if !escaped => depth += 1,
+            '}' if !escaped => depth -= 1,
+            _ => {}
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    depth
+}
+
+/// Highlight the entry types, keys and field names of BibLaTeX as written by this program.
+/// Anything else, such as the continuation lines of multi-line values, is left plain.
+pub fn highlight_biblatex(bib: &str) -> String {
+    lazy_static! {
+        static ref ENTRY: Regex = Regex::new(r"^@(\w+)\{([^,]*),").unwrap();
+        static ref FIELD_NAME: Regex = Regex::new(r"^    ([\w-]+) = ").unwrap();
+    }
+    let mut highlighted = String::with_capacity(bib.len());
+    let mut depth = 0;
+    for line in bib.split_inclusive('\n') {
+        let c = match depth {
+            0 => ENTRY.captures(line),
+            1 => FIELD_NAME.captures(line),
+            _ => None,
+        };
+        match c {
+            Some(c) if depth == 0 => {
+                highlighted += &format!(
+                    "@{}{}{}{{{}{}{},",
+                    ENTRY_TYPE, &c[1], RESET, KEY, &c[2], RESET
+                );
+                highlighted += &line[c[0].len()..];
+            }
+            Some(c) => {
+                highlighted += &format!("    {}{}{} = ", FIELD, &c[1], RESET);
+                highlighted += &line[c[0].len()..];
+            }
+            None => highlighted += line,
+        }
+        depth += brace_depth_change(line);
+    }
+    highlighted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight() {
+        let bib =
+            "@article{smith2020,\n    title = {A = B},\n    note = {Line one\n    x = y},\n}\n";
+        assert_eq!(
+            highlight_biblatex(bib),
+            "@\x1b[1;35marticle\x1b[0m{\x1b[1;33msmith2020\x1b[0m,\n    \x1b[36mtitle\x1b[0m = {A = B},\n    \
+             \x1b[36mnote\x1b[0m = {Line one\n    x = y},\n}\n"
+        );
+        assert!(!ColorChoice::Never.enabled());
+    }
+}
@@ -1,8 +1,152 @@
 use super::csl_fields as csl;
 use super::*;
 use regex::Regex;
+use std::num::NonZeroUsize;
 use tex_tools::biblatex::entry::{self, Entry};
-use tex_tools::biblatex::types::{self, Date, Name};
+use tex_tools::biblatex::types::{self, Date, Month, Name};
+use tex_tools::crossref::{collapse_arrays, SCALAR_FIELDS};
+
+#[derive(Args, Debug, Clone, Default, Serialize)]
+pub struct ConvertOptions {
+    /// Fail instead of warning when an arXiv category is not in the arXiv subject taxonomy
+    #[clap(long)]
+    strict_arxiv_categories: bool,
+
+    /// Fail instead of warning when a page range ends before it starts, e.g. `200-100`
+    #[clap(long)]
+    strict_page_ranges: bool,
+
+    /// Write months as BibLaTeX month macros (`jan`, `feb`, ...) instead of numbers
+    #[clap(long)]
+    month_macros: bool,
+
+    /// Copy these CSL fields into the output when they are not otherwise converted.  Use
+    /// `CSL=NAME` to write a field under a different BibLaTeX name, e.g. `publisher-place=location`.
+    #[clap(long, value_name = "FIELD", use_value_delimiter = true)]
+    keep_fields: Vec<KeepField>,
+
+    /// Replace journal titles with their ISO 4 abbreviations, warning about journals which are
+    /// not in the bundled table or the `--journal-abbreviations` file.
+    #[clap(long)]
+    abbreviate_journal: bool,
+
+    /// Additional journal abbreviations, one `Full Title;Abbreviation` pair per line (JabRef's
+    /// format).  These take precedence over the bundled table.  Implies --abbreviate-journal.
+    #[clap(long, value_name = "PATH")]
+    journal_abbreviations: Option<PathBuf>,
+
+    /// Group conference papers which share a booktitle and year under a single @proceedings
+    /// entry, which the papers reference with `crossref`.
+    #[clap(long)]
+    crossref_proceedings: bool,
+
+    /// Keep at most this many authors, ending the list with `others`, which biber renders as
+    /// "et al."
+    #[clap(long, value_name = "N")]
+    max_authors: Option<NonZeroUsize>,
+
+    /// Drop Zotero's "cheater syntax" from notes: lines like `DOI: 10.1000/xyz` and inline
+    /// `{:original-date: 1900}`, which hold fields Zotero has no place for.
+    #[clap(long)]
+    strip_note_metadata: bool,
+
+    /// Write this CSL field, such as a PDF attachment path exported by Zotero, to the BibLaTeX
+    /// `file` field.  The value is written verbatim, without escaping.
+    #[clap(long, value_name = "FIELD")]
+    file_from: Option<String>,
+
+    /// Write classic BibTeX field names where BibLaTeX renamed them, such as `journal` instead of
+    /// `journaltitle`, for styles which expect them.
+    #[clap(long)]
+    bibtex_fields: bool,
+
+    /// What to do with characters which have no LaTeX equivalent: write them as UTF-8 and rely
+    /// on biber, replace them with `?`, or fail.
+    #[clap(arg_enum, long, value_name = "POLICY", default_value_t = UnknownChars::Error)]
+    unknown_chars: UnknownChars,
+
+    /// Loaded from `journal_abbreviations`, whose contents are part of the fingerprint instead
+    #[clap(skip)]
+    #[serde(skip)]
+    journal_table: journal_abbreviations::Table,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ArgEnum, Serialize)]
+pub enum UnknownChars {
+    Passthrough,
+    Substitute,
+    #[default]
+    Error,
+}
+
+impl From<UnknownChars> for UnknownCharPolicy {
+    fn from(u: UnknownChars) -> Self {
+        match u {
+            UnknownChars::Passthrough => UnknownCharPolicy::Passthrough,
+            UnknownChars::Substitute => UnknownCharPolicy::Substitute,
+            UnknownChars::Error => UnknownCharPolicy::Error,
+        }
+    }
+}
+
+impl ConvertOptions {
+    /// Load any tables named by the options.  Must be called before converting entries.
+    pub fn load_tables(&mut self) -> Result<()> {
+        if let Some(path) = &self.journal_abbreviations {
+            self.journal_table = journal_abbreviations::Table::load(path)?;
+            self.abbreviate_journal = true;
+        }
+        Ok(())
+    }
+
+    /// The field names to write the output with
+    pub fn field_names(&self) -> biblatex::FieldNames {
+        match self.bibtex_fields {
+            true => biblatex::FieldNames::Bibtex,
+            false => biblatex::FieldNames::Biblatex,
+        }
+    }
+
+    /// The policy to write characters which have no LaTeX equivalent with
+    pub fn unknown_char_policy(&self) -> UnknownCharPolicy {
+        self.unknown_chars.into()
+    }
+
+    /// A description of everything which affects the converted output: the version of this
+    /// program, the options and the contents of the `--journal-abbreviations` file.
+    pub fn fingerprint(&self) -> Result<String> {
+        let mut s = format!(
+            "{} {}",
+            env!("CARGO_PKG_VERSION"),
+            serde_json::to_string(self)?
+        );
+        if let Some(path) = &self.journal_abbreviations {
+            s += &std::fs::read_to_string(path).context_read(path)?;
+        }
+        Ok(s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct KeepField {
+    csl: String,
+    biblatex: String,
+}
+
+impl std::str::FromStr for KeepField {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (csl, biblatex) = s.split_once('=').unwrap_or((s, s));
+        if csl.is_empty() || biblatex.is_empty() {
+            return Err(anyhow!("expected FIELD or CSL=NAME, got `{}`", s));
+        }
+        Ok(KeepField {
+            csl: csl.to_string(),
+            biblatex: biblatex.to_ascii_lowercase(),
+        })
+    }
+}
 
 trait ExpectField {
     fn try_field(&mut self, f: &str) -> Option<JsonValue>;
@@ -42,9 +186,58 @@ impl ExpectField for CslEntry {
 #[instrument(level = "trace", skip_all)]
 fn convert_name(name: JsonValue) -> Result<Name> {
     let mut name = name.expect_object()?;
-    let given = name.require_field("given")?.expect_string()?;
+    if let Some(literal) = name.try_field_then("literal", JsonValue::expect_string)? {
+        return Ok(Name::literal(literal));
+    }
+    let given = name
+        .try_field_then("given", JsonValue::expect_string)?
+        .unwrap_or_default();
     let family = name.require_field("family")?.expect_string()?;
-    Ok(Name::new(given, family))
+    let (given, trailing) = split_trailing_particle(given);
+    let dropping = name.try_field_then("dropping-particle", JsonValue::expect_string)?;
+    let non_dropping = name.try_field_then("non-dropping-particle", JsonValue::expect_string)?;
+    // CSL writes the particles as "given dropping non-dropping family"; biber only has one
+    // prefix, so they are joined in that order.
+    let particles: Vec<_> = [trailing, dropping, non_dropping]
+        .into_iter()
+        .flatten()
+        .collect();
+    let mut n = Name::new(given, family);
+    if !particles.is_empty() {
+        n = n.with_particle(particles.join(" "));
+    }
+    if let Some(s) = name.try_field_then("suffix", JsonValue::expect_string)? {
+        n = n.with_suffix(s);
+    }
+    // CSL's hint that the name is written family name first, e.g. "Mao Zedong"
+    let static_ordering = match name.try_field("static-ordering") {
+        Some(JsonValue::Bool(b)) => b,
+        Some(JsonValue::Number(x)) => x.as_f64() != Some(0.0),
+        Some(JsonValue::String(s)) => !matches!(s.as_str(), "" | "0" | "false"),
+        _ => false,
+    };
+    if static_ordering {
+        n = n.with_family_first();
+    }
+    Ok(n)
+}
+
+/// Some exporters leave a dropping particle at the end of the given names ("Ludwig van"), which
+/// biber would then abbreviate as a given name ("L. v.").  Splits off trailing lowercase words,
+/// keeping at least one given name.
+fn split_trailing_particle(given: String) -> (String, Option<String>) {
+    let words: Vec<_> = given.split_whitespace().collect();
+    let n = words
+        .iter()
+        .skip(1)
+        .rev()
+        .take_while(|w| w.starts_with(|c: char| c.is_lowercase()))
+        .count();
+    if n == 0 {
+        return (given, None);
+    }
+    let split = words.len() - n;
+    (words[..split].join(" "), Some(words[split..].join(" ")))
 }
 
 #[instrument(level = "trace", skip_all)]
@@ -53,6 +246,37 @@ fn convert_name_list(list: JsonValue) -> Result<types::List<Name>> {
     names.map(types::List)
 }
 
+/// Like [`convert_name_list`], but truncated to `--max-authors`
+fn convert_author_list(list: JsonValue, options: &ConvertOptions) -> Result<types::List<Name>> {
+    let mut names = convert_name_list(list)?;
+    if let Some(max) = options.max_authors {
+        names.truncate_with_others(max.get());
+    }
+    Ok(names)
+}
+
+/// The authors and editors of an entry type which allows editors instead of authors, as for an
+/// editorial or a standard.  Without authors, the author list is empty and left out of the
+/// output.
+fn convert_author_or_editor(
+    id: &str,
+    e: &mut CslEntry,
+    options: &ConvertOptions,
+) -> Result<(types::List<Name>, Option<types::List<Name>>)> {
+    let author = e.try_field_then(csl::AUTHOR, |a| convert_author_list(a, options))?;
+    let editor = e
+        .try_field_then(csl::EDITOR, convert_name_list)?
+        .filter(|names| !names.0.is_empty());
+    match author {
+        Some(author) if !author.0.is_empty() => Ok((author, editor)),
+        _ if editor.is_some() => {
+            debug!("no authors, using the editors");
+            Ok((types::List(Vec::new()), editor))
+        }
+        _ => bail!("entry `{}` has neither an `author` nor an `editor`", id),
+    }
+}
+
 #[instrument(level = "trace", skip_all)]
 fn convert_date(date: JsonValue) -> Result<Date> {
     #[instrument(level = "trace", skip_all)]
@@ -75,13 +299,19 @@ fn convert_date(date: JsonValue) -> Result<Date> {
         let month = parts
             .pop()
             .map(|v| v.expect_uint().map(|y| y as i32))
-            .transpose()?;
+            .transpose()?
+            // CSL-JSON uses 13 to 16 for seasons
+            .map(|m| if (13..=16).contains(&m) { m + 8 } else { m });
         let day = parts
             .pop()
             .map(|v| v.expect_uint().map(|y| y as i32))
             .transpose()?;
 
-        Ok(Date { year, month, day })
+        Ok(Date {
+            year: year.into(),
+            month,
+            day,
+        })
     }
 
     #[instrument(level = "trace", skip_all)]
@@ -117,8 +347,9 @@ fn convert_date(date: JsonValue) -> Result<Date> {
                         .get(1)
                         .unwrap()
                         .as_str()
-                        .parse()
-                        .context("failed to parse year")?,
+                        .parse::<types::Int>()
+                        .context("failed to parse year")?
+                        .into(),
                     month: c.get(2).map(|s| parse_month(s.as_str())).transpose()?,
                     day: c.get(3).map(|s| parse_date(s.as_str())).transpose()?,
                 });
@@ -131,50 +362,199 @@ fn convert_date(date: JsonValue) -> Result<Date> {
         )
     }
 
-    let mut date = date.expect_object()?;
-    if let Some(parts) = date.remove("date-parts") {
-        return convert_date_parts(parts);
+    #[instrument(level = "trace", skip_all)]
+    fn convert_literal_date(literal: JsonValue) -> Result<Date> {
+        lazy_static! {
+            static ref YEAR: Regex = Regex::new(r"(?:^|\D)(\d{4})(?:\D|$)").unwrap();
+        }
+
+        let s = literal.expect_string()?;
+        let year: types::Int = match YEAR.captures(&s) {
+            Some(c) => c.get(1).unwrap().as_str().parse()?,
+            None => {
+                warn!(literal = %s, "no year found in literal date, writing it as the year");
+                return Ok(Date {
+                    year: types::Year::Literal(s.trim().into()),
+                    month: None,
+                    day: None,
+                });
+            }
+        };
+        let lower = s.to_lowercase();
+        let season = SEASONS
+            .iter()
+            .find(|(name, _)| lower.contains(name))
+            .map(|&(_, code)| code);
+        warn!(literal = %s, "only the year and season of a literal date are kept");
+        Ok(Date {
+            year: year.into(),
+            month: season,
+            day: None,
+        })
     }
-    if let Some(raw) = date.remove("raw") {
-        return convert_raw_date(raw);
+
+    let mut date = date.expect_object()?;
+    let season = date.remove("season");
+    let mut converted = if let Some(parts) = date.remove("date-parts") {
+        convert_date_parts(parts)?
+    } else if let Some(raw) = date.remove("raw") {
+        convert_raw_date(raw)?
+    } else if let Some(literal) = date.remove("literal") {
+        convert_literal_date(literal)?
+    } else {
+        bail!("date fields must have a `date-parts`, `raw` or `literal` property");
+    };
+
+    if let Some(season) = season {
+        if converted.month.is_none() {
+            converted.month = Some(convert_season(season)?);
+        }
     }
+    Ok(converted)
+}
+
+/// Season names and their BibLaTeX codes
+const SEASONS: [(&str, types::Int); 5] = [
+    ("spring", 21),
+    ("summer", 22),
+    ("autumn", 23),
+    ("fall", 23),
+    ("winter", 24),
+];
 
-    bail!("date fields must have either a `date-parts` or `raw` property");
+/// Convert a CSL `season`, which may be 1 to 4, a season name or one of the month-like codes, to
+/// the BibLaTeX season code.
+fn convert_season(season: JsonValue) -> Result<types::Int> {
+    let code = match season {
+        JsonValue::Number(n) => match n.as_i64() {
+            Some(s @ 1..=4) => s as types::Int + 20,
+            Some(s @ 13..=16) => s as types::Int + 8,
+            Some(s @ 21..=24) => s as types::Int,
+            _ => bail!("unknown season {}", n),
+        },
+        JsonValue::String(s) => {
+            let lower = s.trim().to_lowercase();
+            match SEASONS.iter().find(|(name, _)| *name == lower) {
+                Some(&(_, code)) => code,
+                None => match lower.parse::<JsonValue>() {
+                    Ok(n @ JsonValue::Number(_)) => convert_season(n)?,
+                    _ => bail!("unknown season `{}`", s),
+                },
+            }
+        }
+        v => bail!("expected a season, found {}", json_type_name(&v)),
+    };
+    Ok(code)
 }
 
-#[instrument(level = "trace", skip_all)]
-fn convert_page_range(v: JsonValue) -> Result<types::Range> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"^(\d+)-(\d+)$").unwrap();
+/// The year an entry was issued, or `None` if it has no `issued` date or the date is a literal
+/// without a year.
+pub fn issued_year(e: &CslEntry) -> Result<Option<types::Int>> {
+    let date = e
+        .get(csl::ISSUED)
+        .map(|d| convert_date(d.clone()))
+        .transpose()?;
+    Ok(date.and_then(|d| d.year.number()))
+}
+
+/// The `date` field for an issued date with a season, which BibLaTeX takes the year and season
+/// from, overriding `year`.
+fn season_date(date: Date) -> Option<Date> {
+    date.season().map(|_| date)
+}
+
+fn convert_month(month: Option<types::Int>, options: &ConvertOptions) -> Option<Month> {
+    let month = month?;
+    if (21..=24).contains(&month) {
+        // BibLaTeX months can't hold seasons, they go in `date` instead
+        return None;
     }
+    let month = Month::from(month);
+    if !options.month_macros {
+        return Some(month);
+    }
+    if month.macro_name().is_none() {
+        warn!(
+            month = month.number,
+            "month out of range, using numeric form"
+        );
+        return Some(month);
+    }
+    Some(month.as_macro())
+}
+
+/// With `--abbreviate-journal`, the abbreviation from the tables, or else the short title given in
+/// the CSL entry.
+fn abbreviate_journal(title: String, short: Option<&str>, options: &ConvertOptions) -> String {
+    if !options.abbreviate_journal {
+        return title;
+    }
+    match options.journal_table.abbreviate(&title).or(short) {
+        Some(abbrev) => abbrev.to_string(),
+        None => {
+            warn!(journal = %title, "no abbreviation found for journal");
+            title
+        }
+    }
+}
 
+/// Convert a CSL page range: a single page (`7`), a range (`100-200`, or `100-` if open-ended),
+/// or a comma-separated list of these (`12, 15-18, 22`).
+#[instrument(level = "trace", skip_all)]
+fn convert_page_range(v: JsonValue, strict: bool) -> Result<types::Range> {
     let range = v.expect_string()?;
-    if let Ok(i) = range.parse() {
-        return Ok(types::Range::Single(i));
+    if !range.contains(',') {
+        return parse_page_range(&range, strict);
     }
-    let make_err_ctx = || format!("unable to parse `{}` as a range", &range);
-    let make_err = || anyhow::Error::msg(make_err_ctx());
+    let mut ranges = range
+        .split(',')
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(|r| parse_page_range(r, strict))
+        .collect::<Result<Vec<_>>>()
+        .with_context(|| format!("unable to parse `{}` as a list of ranges", &range))?;
+    match ranges.len() {
+        0 => bail!("empty page range `{}`", &range),
+        1 => Ok(ranges.pop().unwrap()),
+        _ => Ok(types::Range::List(ranges)),
+    }
+}
 
-    let captures = RE.captures(&range).ok_or_else(make_err)?;
+/// Parse a single page or range: `12`, `12-15`, the open-ended `12-` or `-15`.  Hyphens and en
+/// dashes are accepted.
+#[instrument(level = "trace", skip_all)]
+fn parse_page_range(range: &str, strict: bool) -> Result<types::Range> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(\d+)?\s*(?:([-–]+)\s*(\d+)?)?$").unwrap();
+    }
 
-    let start: i32 = captures
-        .get(1)
-        .unwrap()
-        .as_str()
-        .parse()
-        .with_context(make_err_ctx)?;
+    let make_err_ctx = || format!("unable to parse `{}` as a range", range);
+    let make_err = || anyhow::Error::msg(make_err_ctx());
 
-    let end: i32 = captures
-        .get(2)
-        .unwrap()
-        .as_str()
-        .parse()
-        .with_context(make_err_ctx)?;
+    let captures = RE.captures(range.trim()).ok_or_else(make_err)?;
+    let parse = |i: usize| -> Result<Option<types::Int>> {
+        captures
+            .get(i)
+            .map(|m| m.as_str().parse())
+            .transpose()
+            .with_context(make_err_ctx)
+    };
+    let is_range = captures.get(2).is_some();
 
-    Ok(types::Range::Multi {
-        start,
-        end: Some(end),
-    })
+    match (parse(1)?, parse(3)?) {
+        (Some(start), _) if !is_range => Ok(types::Range::Single(start)),
+        (None, Some(end)) => Ok(types::Range::UpTo(end)),
+        (Some(start), end) => {
+            if matches!(end, Some(end) if end < start) {
+                if strict {
+                    bail!("page range `{}` ends before it starts", range);
+                }
+                warn!(range = %range, "page range ends before it starts");
+            }
+            Ok(types::Range::Multi { start, end })
+        }
+        (None, None) => Err(make_err()),
+    }
 }
 
 fn take_optional_string_field<T: From<String>>(e: &mut CslEntry, f: &str) -> Result<Option<T>> {
@@ -182,95 +562,381 @@ fn take_optional_string_field<T: From<String>>(e: &mut CslEntry, f: &str) -> Res
     Ok(v.map(From::from))
 }
 
+/// Like [`take_optional_string_field`], but also accepts the numbers which CSL allows for
+/// number-like fields such as `volume`.
+fn take_optional_numeric_field<T: From<String>>(e: &mut CslEntry, f: &str) -> Result<Option<T>> {
+    let v = e.try_field_then(f, |v| match v {
+        JsonValue::Number(n) => Ok(n.to_string()),
+        v => v.expect_string(),
+    })?;
+    Ok(v.map(From::from))
+}
+
 fn take_string_field<T: From<String>>(e: &mut CslEntry, f: &str) -> Result<T> {
     let v = e.require_field_then(f, JsonValue::expect_string)?;
     Ok(v.into())
 }
 
-#[instrument(level = "info", skip(e))]
-fn convert_article(id: String, mut e: CslEntry) -> Result<entry::Article> {
-    let author = e.require_field_then(csl::AUTHOR, convert_name_list)?;
+/// BibLaTeX `langid`s (babel/polyglossia language names) for BCP 47 language tags.  Tags with a
+/// region are matched first, then the primary language subtag alone.
+#[rustfmt::skip]
+const LANGIDS: &[(&str, &str)] = &[
+    ("en-us", "american"), ("en-gb", "british"), ("en-au", "australian"),
+    ("en-ca", "canadian"), ("en-nz", "newzealand"), ("en", "english"),
+    ("de-at", "naustrian"), ("de-ch", "nswissgerman"), ("de", "ngerman"),
+    ("fr-ca", "canadien"), ("fr", "french"),
+    ("pt-br", "brazilian"), ("pt", "portuguese"),
+    ("bg", "bulgarian"), ("ca", "catalan"), ("cs", "czech"), ("da", "danish"), ("el", "greek"),
+    ("es", "spanish"), ("et", "estonian"), ("eu", "basque"), ("fi", "finnish"), ("gl", "galician"),
+    ("hr", "croatian"), ("hu", "magyar"), ("is", "icelandic"), ("it", "italian"), ("la", "latin"),
+    ("lt", "lithuanian"), ("lv", "latvian"), ("nb", "norsk"), ("nl", "dutch"), ("nn", "nynorsk"),
+    ("no", "norsk"), ("pl", "polish"), ("ro", "romanian"), ("ru", "russian"), ("sk", "slovak"),
+    ("sl", "slovene"), ("sr", "serbian"), ("sv", "swedish"), ("tr", "turkish"), ("uk", "ukrainian"),
+];
+
+/// Map a CSL `language` (a BCP 47 tag, or already a babel language name) to a BibLaTeX `langid`.
+fn babel_language(lang: &str) -> Option<&'static str> {
+    let lang = lang.trim().replace('_', "-").to_ascii_lowercase();
+    let primary = lang.split('-').next().unwrap_or_default();
+    LANGIDS
+        .iter()
+        .find(|(tag, _)| *tag == lang)
+        .or_else(|| LANGIDS.iter().find(|(tag, _)| *tag == primary))
+        .or_else(|| LANGIDS.iter().find(|(_, name)| *name == lang))
+        .map(|(_, name)| *name)
+}
+
+fn take_langid(e: &mut CslEntry) -> Result<Option<types::Literal>> {
+    let lang: Option<String> = take_optional_string_field(e, csl::LANGUAGE)?;
+    Ok(lang.and_then(|lang| {
+        let langid = babel_language(&lang);
+        if langid.is_none() {
+            warn!(language = %lang, "no BibLaTeX langid for language, ignoring");
+        }
+        langid.map(From::from)
+    }))
+}
+
+/// Collect the comma-separated CSL `keyword` and the `categories` list into BibLaTeX `keywords`.
+fn take_keywords(e: &mut CslEntry) -> Result<Option<types::Literal>> {
+    let mut keywords: Vec<String> = Vec::new();
+    if let Some(k) = e.try_field_then(csl::KEYWORD, JsonValue::expect_string)? {
+        keywords.extend(k.split(',').map(|k| k.trim().to_string()));
+    }
+    if let Some(c) = e.try_field_then(csl::CATEGORIES, |v| {
+        v.expect_array()?
+            .into_iter()
+            .map(JsonValue::expect_string)
+            .collect::<Result<Vec<_>>>()
+    })? {
+        keywords.extend(c);
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    keywords.retain(|k| !k.is_empty() && seen.insert(k.clone()));
+    if keywords.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(keywords.join(", ").into()))
+}
+
+/// The abstract with any JATS markup from Crossref (`<jats:p>` etc.) removed
+fn take_abstract(e: &mut CslEntry) -> Result<Option<types::Literal>> {
+    let text: Option<String> = take_optional_string_field(e, csl::ABSTRACT)?;
+    let text = text.map(|t| strip_markup(&t));
+    Ok(text.filter(|t| !t.is_empty()).map(From::from))
+}
+
+/// The date and title of the original of a reprinted or translated work, for BibLaTeX's
+/// `origdate` and `origtitle`.  Crossref gives `original-title` as an array, of which the first
+/// is kept.
+fn take_original(e: &mut CslEntry) -> Result<(Option<Date>, Option<types::Literal>)> {
+    collapse_arrays(e, [csl::ORIGINAL_TITLE]);
+    let date = e.try_field_then(csl::ORIGINAL_DATE, convert_date)?;
+    let title = take_optional_string_field(e, csl::ORIGINAL_TITLE)?;
+    Ok((date, title))
+}
+
+/// Whether `key`, from a `key: value` line of a note, is Zotero metadata: the name of a CSL
+/// field other than `note` (in any case), or a Better BibTeX `tex.field`.
+fn is_note_metadata_key(key: &str) -> bool {
+    key.starts_with("tex.")
+        || csl::ALL
+            .iter()
+            .any(|&f| f != csl::NOTE && f.eq_ignore_ascii_case(key))
+}
+
+/// Remove Zotero's "cheater syntax" from a note: `key: value` lines whose key is a CSL field (so
+/// prose like "Published as: ..." or "Note: retracted in 2021" is kept), and inline
+/// `{:key: value}`.
+fn strip_note_metadata(note: &str) -> String {
+    lazy_static! {
+        static ref LINE: Regex = Regex::new(r"^\s*([A-Za-z][\w.-]*):\s*\S").unwrap();
+        static ref INLINE: Regex = Regex::new(r"[ \t]?\{:([\w.-]+):[^}]*\}").unwrap();
+    }
+    for c in INLINE.captures_iter(note) {
+        debug!(field = &c[1], "dropping metadata from note");
+    }
+    let note = INLINE.replace_all(note, "");
+    let lines: Vec<_> = note
+        .lines()
+        .filter(|line| match LINE.captures(line) {
+            Some(c) if is_note_metadata_key(&c[1]) => {
+                debug!(field = &c[1], "dropping metadata from note");
+                false
+            }
+            _ => true,
+        })
+        .map(str::trim_end)
+        .collect();
+    lines.join("\n").trim().to_string()
+}
+
+/// The note, without Zotero metadata if `--strip-note-metadata` was given
+fn take_note(e: &mut CslEntry, options: &ConvertOptions) -> Result<Option<types::Note>> {
+    let note: Option<String> = take_optional_string_field(e, csl::NOTE)?;
+    let note = note.map(|n| match options.strip_note_metadata {
+        true => strip_note_metadata(&n),
+        false => n.trim().to_string(),
+    });
+    Ok(note.filter(|n| !n.is_empty()).map(From::from))
+}
+
+/// Check all the required CSL fields up front, so every missing field is reported at once.
+fn check_required_fields(id: &str, e: &CslEntry, fields: &[&str]) -> Result<()> {
+    let missing: Vec<_> = fields
+        .iter()
+        .filter(|f| !e.contains_key(**f))
+        .map(|f| format!("`{}`", f))
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "entry `{}` is missing required CSL field(s): {}",
+            id,
+            missing.join(", ")
+        );
+    }
+    Ok(())
+}
+
+// BibLaTeX's `number` means something different for each entry type, so it is taken from a
+// different CSL field:
+//
+// - @article: `issue`.  CSL's `number` is then the article number, which becomes `eid`.
+// - @report (reports and working papers): `number`, the report number.
+// - @inproceedings, @book and @dataset: `collection-number`, the number in the series.
+
+#[instrument(level = "info", skip(e, options))]
+fn convert_article(
+    id: String,
+    e: &mut CslEntry,
+    options: &ConvertOptions,
+) -> Result<entry::Article> {
+    check_required_fields(&id, e, &[csl::TITLE, csl::CONTAINER_TITLE, csl::ISSUED])?;
+    let (author, editor) = convert_author_or_editor(&id, e, options)?;
     let title = e.require_field_then(csl::TITLE, |t| t.expect_string().map(From::from))?;
-    let journal_title =
-        e.require_field_then(csl::CONTAINER_TITLE, |t| t.expect_string().map(From::from))?;
+    let short_journal: Option<String> =
+        match take_optional_string_field(e, csl::CONTAINER_TITLE_SHORT)? {
+            Some(s) => Some(s),
+            None => take_optional_string_field(e, csl::JOURNAL_ABBREVIATION)?,
+        };
+    let journal_title: String = take_string_field(e, csl::CONTAINER_TITLE)?;
+    let journal_title = abbreviate_journal(journal_title, short_journal.as_deref(), options);
+    // Crossref often repeats the full title as the short one, and abbreviating may have
+    // replaced the full title with the short one
+    let short_journal = short_journal.filter(|s| s != &journal_title);
     let date = e.require_field_then(csl::ISSUED, convert_date)?;
-    let year = date.year;
+    let year = date.year.clone();
 
-    let mut a = entry::Article::new(id, author, title, journal_title, year);
+    let mut a = entry::Article::new(id, author, title, journal_title.into(), year);
+    a.editor = editor;
 
-    a.month = date.month;
-    a.doi = take_optional_string_field(&mut e, csl::DOI)?;
-    a.number = take_optional_string_field(&mut e, csl::ISSUE)?;
-    a.pages = e.try_field_then(csl::PAGE, convert_page_range)?;
-    a.volume = take_optional_string_field(&mut e, csl::VOLUME)?;
+    a.month = convert_month(date.month, options);
+    a.date = season_date(date);
+    a.langid = take_langid(e)?;
+    a.keywords = take_keywords(e)?;
+    a.abstract_ = take_abstract(e)?;
+    a.note = take_note(e, options)?;
+    (a.orig_date, a.orig_title) = take_original(e)?;
+    a.short_journal = short_journal.map(From::from);
+    a.doi = take_optional_string_field(e, csl::DOI)?;
+    a.issn = take_optional_string_field(e, csl::ISSN)?;
+    // The issue, not CSL's `number` (see above)
+    a.number = take_optional_string_field(e, csl::ISSUE)?;
+    a.pages = e.try_field_then(csl::PAGE, |v| {
+        convert_page_range(v, options.strict_page_ranges)
+    })?;
+    if a.pages.is_none() {
+        // Electronic-only journals give an article number instead of pages
+        a.eid = take_optional_numeric_field(e, csl::NUMBER)?;
+    }
+    a.volume = take_optional_string_field(e, csl::VOLUME)?;
     Ok(a)
 }
 
-#[instrument(level = "info", skip(e))]
-fn convert_thesis(id: String, mut e: CslEntry) -> Result<entry::Thesis> {
-    let author = e.require_field_then(csl::AUTHOR, convert_name_list)?;
-    let title = take_string_field(&mut e, csl::TITLE)?;
+#[instrument(level = "info", skip(e, options))]
+fn convert_thesis(id: String, e: &mut CslEntry, options: &ConvertOptions) -> Result<entry::Thesis> {
+    check_required_fields(
+        &id,
+        e,
+        &[
+            csl::AUTHOR,
+            csl::TITLE,
+            csl::ISSUED,
+            csl::PUBLISHER,
+            csl::GENRE,
+        ],
+    )?;
+    let author = e.require_field_then(csl::AUTHOR, |a| convert_author_list(a, options))?;
+    let title = take_string_field(e, csl::TITLE)?;
     let date = e.require_field_then(csl::ISSUED, convert_date)?;
-    let year = date.year;
-    let institution = types::List(vec![take_string_field(&mut e, csl::PUBLISHER)?]);
+    let year = date.year.clone();
+    let institution = types::List(vec![take_string_field(e, csl::PUBLISHER)?]);
     let kind = e.require_field_then(csl::GENRE, |v| v.expect_string().map(From::from))?;
 
     let mut t = entry::Thesis::new(id, author, title, kind, institution, year);
-    t.month = date.month;
+    t.month = convert_month(date.month, options);
+    t.date = season_date(date);
+    t.langid = take_langid(e)?;
+    t.keywords = take_keywords(e)?;
+    t.abstract_ = take_abstract(e)?;
+    t.note = take_note(e, options)?;
+    (t.orig_date, t.orig_title) = take_original(e)?;
     Ok(t)
 }
 
-#[instrument(level = "info", skip(e))]
-fn convert_conference_paper(id: String, mut e: CslEntry) -> Result<entry::InProceedings> {
-    let author = e.require_field_then(csl::AUTHOR, convert_name_list)?;
-    let title = take_string_field(&mut e, csl::TITLE)?;
+#[instrument(level = "info", skip(e, options))]
+fn convert_conference_paper(
+    id: String,
+    e: &mut CslEntry,
+    options: &ConvertOptions,
+) -> Result<entry::InProceedings> {
+    check_required_fields(&id, e, &[csl::TITLE, csl::ISSUED, csl::CONTAINER_TITLE])?;
+    let (author, editor) = convert_author_or_editor(&id, e, options)?;
+    let title = take_string_field(e, csl::TITLE)?;
     let date = e.require_field_then(csl::ISSUED, convert_date)?;
-    let year = date.year;
-    let book_title = take_string_field(&mut e, csl::CONTAINER_TITLE)?;
+    let year = date.year.clone();
+    let book_title = take_string_field(e, csl::CONTAINER_TITLE)?;
 
     let mut c = entry::InProceedings::new(id, author, title, book_title, year);
-    c.month = date.month;
-    c.doi = take_optional_string_field(&mut e, csl::DOI)?;
-    c.publisher = take_optional_string_field(&mut e, csl::PUBLISHER)?.map(types::List::singleton);
-    c.location =
-        take_optional_string_field(&mut e, csl::PUBLISHER_PLACE)?.map(types::List::singleton);
+    c.editor = editor;
+    c.month = convert_month(date.month, options);
+    c.date = season_date(date);
+    c.langid = take_langid(e)?;
+    c.keywords = take_keywords(e)?;
+    c.abstract_ = take_abstract(e)?;
+    c.note = take_note(e, options)?;
+    (c.orig_date, c.orig_title) = take_original(e)?;
+    c.doi = take_optional_string_field(e, csl::DOI)?;
+    c.publisher = take_optional_string_field(e, csl::PUBLISHER)?.map(types::List::singleton);
+    c.location = take_optional_string_field(e, csl::PUBLISHER_PLACE)?.map(types::List::singleton);
+    c.series = take_optional_string_field(e, csl::COLLECTION_TITLE)?;
+    c.number = take_optional_numeric_field(e, csl::COLLECTION_NUMBER)?;
+    c.volume = take_optional_numeric_field(e, csl::VOLUME)?;
+    c.volumes = take_optional_numeric_field(e, csl::NUMBER_OF_VOLUMES)?;
+    c.part = take_optional_numeric_field(e, csl::PART)?;
+
+    // The conference itself, as opposed to the proceedings in `booktitle`.  `event` is the CSL
+    // 1.0 name for `event-title`, and Crossref sometimes gives it as an object.
+    let (event_name, event_location) = match e.remove(csl::EVENT) {
+        Some(JsonValue::Object(mut event)) => (
+            event.try_field_then("name", JsonValue::expect_string)?,
+            event.try_field_then("location", JsonValue::expect_string)?,
+        ),
+        Some(v) => (
+            Some(v.expect_string().context("error in field `event`")?),
+            None,
+        ),
+        None => (None, None),
+    };
+    c.event_title = take_optional_string_field(e, csl::EVENT_TITLE)?
+        .or(event_name)
+        .map(From::from);
+    c.venue = take_optional_string_field(e, csl::EVENT_PLACE)?
+        .or(event_location)
+        .map(From::from);
+    c.event_date = e.try_field_then(csl::EVENT_DATE, convert_date)?;
     Ok(c)
 }
 
-#[instrument(level = "info", skip(e))]
-fn convert_report(id: String, mut e: CslEntry) -> Result<entry::Report> {
-    let author = e.require_field_then(csl::AUTHOR, convert_name_list)?;
-    let title = take_string_field(&mut e, csl::TITLE)?;
+#[instrument(level = "info", skip(e, options))]
+fn convert_report(id: String, e: &mut CslEntry, options: &ConvertOptions) -> Result<entry::Report> {
+    check_required_fields(
+        &id,
+        e,
+        &[
+            csl::AUTHOR,
+            csl::TITLE,
+            csl::ISSUED,
+            csl::PUBLISHER,
+            csl::GENRE,
+        ],
+    )?;
+    let author = e.require_field_then(csl::AUTHOR, |a| convert_author_list(a, options))?;
+    let title = take_string_field(e, csl::TITLE)?;
     let date = e.require_field_then(csl::ISSUED, convert_date)?;
-    let year = date.year;
-    let institution = types::List(vec![take_string_field(&mut e, csl::PUBLISHER)?]);
+    let year = date.year.clone();
+    let institution = types::List(vec![take_string_field(e, csl::PUBLISHER)?]);
     let kind = e.require_field_then(csl::GENRE, |v| v.expect_string().map(From::from))?;
 
     let mut r = entry::Report::new(id, author, title, kind, institution, year);
-    r.month = date.month;
+    r.month = convert_month(date.month, options);
+    r.date = season_date(date);
+    r.langid = take_langid(e)?;
+    r.keywords = take_keywords(e)?;
+    r.abstract_ = take_abstract(e)?;
+    r.note = take_note(e, options)?;
+    (r.orig_date, r.orig_title) = take_original(e)?;
+    r.number = take_optional_numeric_field(e, csl::NUMBER)?;
     Ok(r)
 }
 
-#[instrument(level = "info", skip(e))]
-fn convert_working_paper(id: String, mut e: CslEntry) -> Result<entry::Report> {
-    let author = e.require_field_then(csl::AUTHOR, convert_name_list)?;
-    let title = take_string_field(&mut e, csl::TITLE)?;
+#[instrument(level = "info", skip(e, options))]
+fn convert_working_paper(
+    id: String,
+    e: &mut CslEntry,
+    options: &ConvertOptions,
+) -> Result<entry::Report> {
+    check_required_fields(
+        &id,
+        e,
+        &[csl::AUTHOR, csl::TITLE, csl::ISSUED, csl::PUBLISHER],
+    )?;
+    let author = e.require_field_then(csl::AUTHOR, |a| convert_author_list(a, options))?;
+    let title = take_string_field(e, csl::TITLE)?;
     let date = e.require_field_then(csl::ISSUED, convert_date)?;
-    let year = date.year;
-    let institution = types::List(vec![take_string_field(&mut e, csl::PUBLISHER)?]);
+    let year = date.year.clone();
+    let institution = types::List(vec![take_string_field(e, csl::PUBLISHER)?]);
     let kind = "Working paper".to_string().into();
+    // Working papers are often found through SSRN or RePEc, which are cited by their identifiers.
+    // The publisher is the institution, so it doesn't identify the server.
+    let url = e.get(csl::URL).and_then(JsonValue::as_str);
+    let doi = e.get(csl::DOI).and_then(JsonValue::as_str);
+    let eprint =
+        PreprintServer::detect(url, doi, None).and_then(|s| Some((s, s.eprint_id(url, doi)?)));
 
     let mut r = entry::Report::new(id, author, title, kind, institution, year);
-    r.month = date.month;
-    r.number = take_optional_string_field(&mut e, csl::NUMBER)?;
-    r.url = take_optional_string_field(&mut e, csl::URL)?;
+    r.month = convert_month(date.month, options);
+    r.date = season_date(date);
+    r.langid = take_langid(e)?;
+    r.keywords = take_keywords(e)?;
+    r.abstract_ = take_abstract(e)?;
+    r.note = take_note(e, options)?;
+    (r.orig_date, r.orig_title) = take_original(e)?;
+    r.number = take_optional_numeric_field(e, csl::NUMBER)?;
+    if let Some((server, eprint)) = eprint {
+        debug!(?server, eprint=%eprint, "recognised preprint server");
+        r.eprint = Some(eprint.into());
+        r.eprint_type = Some(server.eprint_type().into());
+    }
+    r.url = take_optional_string_field(e, csl::URL)?;
     Ok(r)
 }
 
 #[instrument(level="info", skip(s), fields(s=?s.as_ref()))]
 fn parse_arxiv_category(s: impl AsRef<str>) -> Result<String> {
     lazy_static! {
-        static ref RE: Regex = Regex::new(r"\(([a-z]+\.[A-Z]+)\)").unwrap();
+        static ref RE: Regex = Regex::new(r"\(([a-z\-]+(?:\.[A-Za-z\-]+)?)\)").unwrap();
     }
 
     let s = s.as_ref();
@@ -280,18 +946,60 @@ fn parse_arxiv_category(s: impl AsRef<str>) -> Result<String> {
     bail!("failed to parse arXiv category: {}", s);
 }
 
-#[instrument(level = "info", skip(e))]
-fn convert_arxiv_paper(id: String, mut e: CslEntry) -> Result<entry::Misc> {
-    let author = e.require_field_then(csl::AUTHOR, convert_name_list)?;
-    let title = take_string_field(&mut e, csl::TITLE)?;
+fn check_arxiv_category(category: &str, strict: bool) -> Result<()> {
+    if !arxiv_categories::is_known(category) {
+        if strict {
+            bail!("unknown arXiv category: {}", category);
+        }
+        warn!(category, "unknown arXiv category");
+    }
+    Ok(())
+}
+
+/// Extract the canonical arXiv identifier and version suffix (if any) from a URL.  Both the
+/// new `YYMM.NNNNN` and old `archive(.SC)/YYMMNNN` identifier schemes are supported.
+#[instrument(level = "info")]
+fn parse_arxiv_id(url: &str) -> Result<(String, Option<String>)> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"(?:^|[/:])(\d{4}\.\d{4,5}|[a-z\-]+(?:\.[A-Z]{2})?/\d{7})(?:v(\d+))?")
+                .unwrap();
+    }
+
+    let c = RE
+        .captures(url)
+        .ok_or_else(|| anyhow!("failed to parse URL for arXiv ID: {}", url))?;
+    let id = c.get(1).unwrap().as_str().to_string();
+    let version = c.get(2).map(|v| v.as_str().to_string());
+    Ok((id, version))
+}
+
+#[instrument(level = "info", skip(e, options))]
+fn convert_arxiv_paper(
+    id: String,
+    e: &mut CslEntry,
+    options: &ConvertOptions,
+) -> Result<entry::Misc> {
+    check_required_fields(
+        &id,
+        e,
+        &[
+            csl::AUTHOR,
+            csl::TITLE,
+            csl::ISSUED,
+            csl::URL,
+            csl::CATEGORIES,
+        ],
+    )?;
+    let author = e.require_field_then(csl::AUTHOR, |a| convert_author_list(a, options))?;
+    let title = take_string_field(e, csl::TITLE)?;
     let date = e.require_field_then(csl::ISSUED, convert_date)?;
-    let mut b = entry::Misc::new(id, author, title, date.year);
+    let mut b = entry::Misc::new(id, author, title, date.year.clone());
+    b.date = season_date(date);
+    b.langid = take_langid(e)?;
 
-    let url: String = take_string_field(&mut e, csl::URL)?;
-    let arxiv_id = url
-        .split("/")
-        .last()
-        .ok_or_else(|| anyhow!("failed to parse URL for arXiv ID: {}", &url))?;
+    let url: String = take_string_field(e, csl::URL)?;
+    let (arxiv_id, url_version) = parse_arxiv_id(&url)?;
 
     b.eprint = Some(arxiv_id.into());
     b.eprint_type = Some("arXiv".into());
@@ -304,56 +1012,481 @@ fn convert_arxiv_paper(id: String, mut e: CslEntry) -> Result<entry::Misc> {
             .expect_string()?;
         parse_arxiv_category(c)
     })?;
+    check_arxiv_category(&main_category, options.strict_arxiv_categories)?;
     b.eprint_class = Some(main_category.into());
-    b.version = take_optional_string_field(&mut e, csl::VERSION)?;
+    b.keywords = take_keywords(e)?;
+    b.abstract_ = take_abstract(e)?;
+    b.note = take_note(e, options)?;
+    b.version = take_optional_string_field(e, csl::VERSION)?.or(url_version.map(From::from));
     Ok(b)
 }
 
-fn convert_book(id: String, mut e: CslEntry) -> Result<entry::Book> {
-    let author = e.require_field_then(csl::AUTHOR, convert_name_list)?;
-    let title = take_string_field(&mut e, csl::TITLE)?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PreprintServer {
+    Arxiv,
+    BioRxiv,
+    MedRxiv,
+    Ssrn,
+    Osf,
+    /// Not a server, but an index of economics working papers
+    Repec,
+}
+
+impl PreprintServer {
+    /// Guess the preprint server from the URL, DOI or publisher of an entry (checked in that order).
+    fn detect(url: Option<&str>, doi: Option<&str>, publisher: Option<&str>) -> Option<Self> {
+        use PreprintServer::*;
+        lazy_static! {
+            // Other servers are named "*arXiv" too (e.g. EarthArXiv), so match arXiv's own domain
+            static ref ARXIV: Regex =
+                Regex::new(r"(?i)(?:^|[/.])arxiv\.org\b|^10\.48550/arxiv\.").unwrap();
+        }
+        const PATTERNS: &[(&str, PreprintServer)] = &[
+            ("osf.io", Osf),
+            ("center for open science", Osf),
+            ("psyarxiv", Osf),
+            ("socarxiv", Osf),
+            ("medrxiv", MedRxiv),
+            ("biorxiv", BioRxiv),
+            ("cold spring harbor", BioRxiv),
+            ("ssrn", Ssrn),
+            ("repec", Repec),
+        ];
+
+        for s in [url, doi, publisher].into_iter().flatten() {
+            if ARXIV.is_match(s) {
+                return Some(Arxiv);
+            }
+            let s = s.to_ascii_lowercase();
+            for (pattern, server) in PATTERNS {
+                if s.contains(pattern) {
+                    return Some(*server);
+                }
+            }
+        }
+        match publisher {
+            Some(p) if p.trim().eq_ignore_ascii_case("arxiv") => Some(Arxiv),
+            _ => None,
+        }
+    }
+
+    fn eprint_type(&self) -> &'static str {
+        match self {
+            PreprintServer::Arxiv => "arXiv",
+            PreprintServer::BioRxiv => "bioRxiv",
+            PreprintServer::MedRxiv => "medRxiv",
+            PreprintServer::Ssrn => "SSRN",
+            PreprintServer::Osf => "OSF",
+            PreprintServer::Repec => "RePEc",
+        }
+    }
+
+    /// Extract the server-specific identifier from the URL or DOI.  For RePEc this is the handle
+    /// without its `RePEc:` prefix, e.g. `nbr:nberwo:12345`, which IDEAS URLs also encode.
+    fn eprint_id(&self, url: Option<&str>, doi: Option<&str>) -> Option<String> {
+        lazy_static! {
+            static ref CSHL_DOI: Regex = Regex::new(r"10\.1101/[0-9.]+").unwrap();
+            static ref SSRN: Regex = Regex::new(r"(?i)(?:ssrn\.|abstract(?:_?id)?=)(\d+)").unwrap();
+            static ref OSF: Regex =
+                Regex::new(r"osf\.io/(?:preprints/[a-z]+/)?([a-z0-9]+)").unwrap();
+            static ref REPEC_HANDLE: Regex =
+                Regex::new(r"(?i)\brepec:([a-z0-9]+:[a-z0-9]+:[^\s/?#]+)").unwrap();
+            static ref IDEAS: Regex = Regex::new(
+                r"(?i)ideas\.repec\.org/[a-z]/([a-z0-9]+)/([a-z0-9]+)/([^/?#]+?)(?:\.html)?(?:[?#]|$)"
+            )
+            .unwrap();
+        }
+
+        let find = |re: &Regex, group: usize| {
+            [doi, url]
+                .into_iter()
+                .flatten()
+                .find_map(|s| re.captures(s))
+                .map(|c| c.get(group).unwrap().as_str().to_string())
+        };
+
+        match self {
+            PreprintServer::Arxiv => None,
+            PreprintServer::BioRxiv | PreprintServer::MedRxiv => find(&CSHL_DOI, 0),
+            PreprintServer::Ssrn => find(&SSRN, 1),
+            PreprintServer::Osf => find(&OSF, 1),
+            PreprintServer::Repec => find(&REPEC_HANDLE, 1).or_else(|| {
+                let c = url.and_then(|url| IDEAS.captures(url))?;
+                Some(format!("{}:{}:{}", &c[1], &c[2], &c[3]))
+            }),
+        }
+    }
+}
+
+/// Preprints from a recognised server become `@misc` entries with `eprint` fields (like arXiv
+/// papers), otherwise they fall back to `@online` with the URL.
+#[instrument(level = "info", skip(e, options))]
+fn convert_preprint(id: String, e: &mut CslEntry, options: &ConvertOptions) -> Result<Entry> {
+    let url = e.get(csl::URL).and_then(JsonValue::as_str);
+    let doi = e.get(csl::DOI).and_then(JsonValue::as_str);
+    let publisher = e.get(csl::PUBLISHER).and_then(JsonValue::as_str);
+    let server = PreprintServer::detect(url, doi, publisher);
+
+    if server == Some(PreprintServer::Arxiv) {
+        return convert_arxiv_paper(id, e, options).map(Entry::Misc);
+    }
+    let eprint = server.and_then(|s| Some((s, s.eprint_id(url, doi)?)));
+    if eprint.is_some() {
+        check_required_fields(&id, e, &[csl::AUTHOR, csl::TITLE, csl::ISSUED])?;
+    } else {
+        check_required_fields(&id, e, &[csl::AUTHOR, csl::TITLE, csl::ISSUED, csl::URL])?;
+    }
+
+    let author = e.require_field_then(csl::AUTHOR, |a| convert_author_list(a, options))?;
+    let title = take_string_field(e, csl::TITLE)?;
     let date = e.require_field_then(csl::ISSUED, convert_date)?;
-    let mut b = entry::Book::new(id, author, title, date.year);
 
-    b.chapter = take_optional_string_field(&mut e, csl::CHAPTER_NUMBER)?;
-    b.doi = take_optional_string_field(&mut e, csl::DOI)?;
-    b.isbn = take_optional_string_field(&mut e, csl::ISBN)?;
-    b.publisher = take_optional_string_field(&mut e, csl::PUBLISHER)?.map(types::List::singleton);
-    b.location =
-        take_optional_string_field(&mut e, csl::PUBLISHER_PLACE)?.map(types::List::singleton);
-    b.pages = e.try_field_then(csl::PAGE, convert_page_range)?;
+    if let Some((server, eprint)) = eprint {
+        debug!(?server, eprint=%eprint, "recognised preprint server");
+        let mut b = entry::Misc::new(id, author, title, date.year.clone());
+        b.date = season_date(date);
+        b.langid = take_langid(e)?;
+        let doi: Option<String> = take_optional_string_field(e, csl::DOI)?;
+        b.doi = doi.filter(|doi| doi != &eprint).map(From::from);
+        b.eprint = Some(eprint.into());
+        b.eprint_type = Some(server.eprint_type().into());
+        b.eprint_class = e
+            .try_field_then(csl::CATEGORIES, |v| {
+                v.expect_array()?
+                    .into_iter()
+                    .next()
+                    .map(JsonValue::expect_string)
+                    .transpose()
+                    .map(|c| c.map(From::from))
+            })?
+            .flatten();
+        b.version = take_optional_string_field(e, csl::VERSION)?;
+        b.keywords = take_keywords(e)?;
+        b.abstract_ = take_abstract(e)?;
+        b.note = take_note(e, options)?;
+        Ok(Entry::Misc(b))
+    } else {
+        warn!("unrecognised preprint server, falling back to @online");
+        let url = take_string_field(e, csl::URL)?;
+        let mut o = entry::Online::new(id, author, title, date.year.clone(), url);
+        o.month = convert_month(date.month, options);
+        o.date = season_date(date);
+        o.langid = take_langid(e)?;
+        o.keywords = take_keywords(e)?;
+        o.abstract_ = take_abstract(e)?;
+        o.note = take_note(e, options)?;
+        o.doi = take_optional_string_field(e, csl::DOI)?;
+        o.organization = take_optional_string_field(e, csl::PUBLISHER)?.map(types::List::singleton);
+        o.version = take_optional_string_field(e, csl::VERSION)?;
+        Ok(Entry::Online(o))
+    }
+}
+
+fn convert_book(id: String, e: &mut CslEntry, options: &ConvertOptions) -> Result<entry::Book> {
+    check_required_fields(&id, e, &[csl::TITLE, csl::ISSUED])?;
+    let (author, editor) = convert_author_or_editor(&id, e, options)?;
+    let title = take_string_field(e, csl::TITLE)?;
+    let date = e.require_field_then(csl::ISSUED, convert_date)?;
+    let mut b = entry::Book::new(id, author, title, date.year.clone());
+    b.date = season_date(date);
+    b.editor = editor;
+    b.langid = take_langid(e)?;
+    b.keywords = take_keywords(e)?;
+    b.abstract_ = take_abstract(e)?;
+    b.note = take_note(e, options)?;
+    (b.orig_date, b.orig_title) = take_original(e)?;
+
+    b.chapter = take_optional_string_field(e, csl::CHAPTER_NUMBER)?;
+    b.doi = take_optional_string_field(e, csl::DOI)?;
+    b.isbn = take_optional_string_field(e, csl::ISBN)?;
+    b.publisher = take_optional_string_field(e, csl::PUBLISHER)?.map(types::List::singleton);
+    b.location = take_optional_string_field(e, csl::PUBLISHER_PLACE)?.map(types::List::singleton);
+    b.pages = e.try_field_then(csl::PAGE, |v| {
+        convert_page_range(v, options.strict_page_ranges)
+    })?;
+    b.series = take_optional_string_field(e, csl::COLLECTION_TITLE)?;
+    b.number = take_optional_numeric_field(e, csl::COLLECTION_NUMBER)?;
+    b.volume = take_optional_numeric_field(e, csl::VOLUME)?;
+    b.volumes = take_optional_numeric_field(e, csl::NUMBER_OF_VOLUMES)?;
+    b.part = take_optional_numeric_field(e, csl::PART)?;
 
     Ok(b)
 }
 
-#[instrument(level = "error", skip(e), fields(id))]
-pub fn csl_to_biblatex(mut e: CslEntry) -> Result<Entry> {
+fn convert_software(
+    id: String,
+    e: &mut CslEntry,
+    options: &ConvertOptions,
+) -> Result<entry::Software> {
+    check_required_fields(&id, e, &[csl::AUTHOR, csl::TITLE, csl::ISSUED])?;
+    let author = e.require_field_then(csl::AUTHOR, |a| convert_author_list(a, options))?;
+    let title = take_string_field(e, csl::TITLE)?;
+    let date = e.require_field_then(csl::ISSUED, convert_date)?;
+    let mut s = entry::Software::new(id, author, title, date.year.clone());
+    s.month = convert_month(date.month, options);
+    s.date = season_date(date);
+    s.langid = take_langid(e)?;
+    s.keywords = take_keywords(e)?;
+    s.abstract_ = take_abstract(e)?;
+    s.note = take_note(e, options)?;
+
+    s.doi = take_optional_string_field(e, csl::DOI)?;
+    s.version = take_optional_string_field(e, csl::VERSION)?;
+    s.publisher = take_optional_string_field(e, csl::PUBLISHER)?.map(types::List::singleton);
+    s.url = take_optional_string_field(e, csl::URL)?;
+    s.url_date = e.try_field_then(csl::ACCESSED, convert_date)?;
+    Ok(s)
+}
+
+fn convert_dataset(
+    id: String,
+    e: &mut CslEntry,
+    options: &ConvertOptions,
+) -> Result<entry::Dataset> {
+    check_required_fields(&id, e, &[csl::TITLE, csl::ISSUED])?;
+    let (author, editor) = convert_author_or_editor(&id, e, options)?;
+    let title = take_string_field(e, csl::TITLE)?;
+    let date = e.require_field_then(csl::ISSUED, convert_date)?;
+    let mut d = entry::Dataset::new(id, author, title, date.year.clone());
+    d.editor = editor;
+    d.month = convert_month(date.month, options);
+    d.date = season_date(date);
+    d.langid = take_langid(e)?;
+    d.keywords = take_keywords(e)?;
+    d.abstract_ = take_abstract(e)?;
+    d.note = take_note(e, options)?;
+
+    d.doi = take_optional_string_field(e, csl::DOI)?;
+    d.version = take_optional_string_field(e, csl::VERSION)?;
+    d.edition = take_optional_numeric_field(e, csl::EDITION)?;
+    d.type_ = take_optional_string_field(e, csl::GENRE)?;
+    d.publisher = take_optional_string_field(e, csl::PUBLISHER)?.map(types::List::singleton);
+    d.location = take_optional_string_field(e, csl::PUBLISHER_PLACE)?.map(types::List::singleton);
+    d.series = take_optional_string_field(e, csl::COLLECTION_TITLE)?;
+    d.number = take_optional_numeric_field(e, csl::COLLECTION_NUMBER)?;
+    d.url = take_optional_string_field(e, csl::URL)?;
+    d.url_date = e.try_field_then(csl::ACCESSED, convert_date)?;
+    Ok(d)
+}
+
+/// Input entries may have arrays where CSL expects a single value, as Crossref gives them (see
+/// [`SCALAR_FIELDS`]).  The first value is kept, and an empty array is treated like a missing
+/// field.
+fn unwrap_array_fields(e: &mut CslEntry) {
+    collapse_arrays(e, SCALAR_FIELDS.iter().copied());
+}
+
+/// The key of the CSL `custom` object which overrides the BibLaTeX entry type
+const BIBLATEX_TYPE: &str = "biblatex-type";
+
+/// Take the BibLaTeX type hint from the entry's `custom` object, e.g.
+/// `"custom": { "biblatex-type": "inproceedings" }`, removing `custom` if nothing else is left in
+/// it.
+fn take_biblatex_type(e: &mut CslEntry) -> Result<Option<String>> {
+    let custom = match e.get_mut(csl::CUSTOM) {
+        Some(JsonValue::Object(custom)) => custom,
+        _ => return Ok(None),
+    };
+    let ty = custom
+        .try_field_then(BIBLATEX_TYPE, JsonValue::expect_string)
+        .with_context(|| format!("error in field `custom.{}`", BIBLATEX_TYPE))?;
+    if custom.is_empty() {
+        e.remove(csl::CUSTOM);
+    }
+    Ok(ty.map(|ty| ty.trim().to_ascii_lowercase()))
+}
+
+#[instrument(level = "error", skip(e, options), fields(id))]
+pub fn csl_to_biblatex(mut e: CslEntry, options: &ConvertOptions) -> Result<Entry> {
     let id = e.require_field(csl::ID)?.expect_string()?;
     tracing::Span::current().record("id", &&*id);
 
     let err_context = format!("failed to convert entry `{}`", id);
     #[inline]
-    fn match_type(id: String, mut e: CslEntry) -> Result<Entry> {
-        match e.require_field(csl::TYPE)?.expect_string()?.as_str() {
-            "article-journal" => convert_article(id, e).map(Entry::Article),
+    fn match_type(id: String, e: &mut CslEntry, options: &ConvertOptions) -> Result<Entry> {
+        let ty = e.require_field(csl::TYPE)?.expect_string()?;
+        if let Some(biblatex_type) = take_biblatex_type(e)? {
+            debug!(csl_type = %ty, biblatex_type = %biblatex_type, "using type from `custom`");
+            return match biblatex_type.as_str() {
+                "article" => convert_article(id, e, options).map(Entry::Article),
+                "book" => convert_book(id, e, options).map(Entry::Book),
+                "dataset" => convert_dataset(id, e, options).map(Entry::Dataset),
+                "inproceedings" => {
+                    convert_conference_paper(id, e, options).map(Entry::InProceedings)
+                }
+                "report" => convert_report(id, e, options).map(Entry::Report),
+                "software" => convert_software(id, e, options).map(Entry::Software),
+                "thesis" => convert_thesis(id, e, options).map(Entry::Thesis),
+                ty => bail!(
+                    "unsupported BibLaTeX type `{}` in `custom.{}`",
+                    ty,
+                    BIBLATEX_TYPE
+                ),
+            };
+        }
+        match ty.as_str() {
+            "article-journal" => convert_article(id, e, options).map(Entry::Article),
             "article" => {
                 let mut ty = e.require_field(csl::GENRE)?.expect_string()?;
                 ty.make_ascii_lowercase();
                 match ty.trim() {
-                    "working paper" => convert_working_paper(id, e).map(Entry::Report),
-                    "arxiv" => convert_arxiv_paper(id, e).map(Entry::Misc),
+                    "working paper" => convert_working_paper(id, e, options).map(Entry::Report),
+                    "arxiv" => convert_arxiv_paper(id, e, options).map(Entry::Misc),
+                    "preprint" | "biorxiv" | "medrxiv" | "ssrn" | "osf" | "repec" => {
+                        convert_preprint(id, e, options)
+                    }
                     unknown => bail!("unknown article sub-type `{}`", unknown),
                 }
             }
-            "thesis" => convert_thesis(id, e).map(Entry::Thesis),
-            "paper-conference" => convert_conference_paper(id, e).map(Entry::InProceedings),
-            "report" => convert_report(id, e).map(Entry::Report),
-            "book" => convert_book(id, e).map(Entry::Book),
+            "thesis" => convert_thesis(id, e, options).map(Entry::Thesis),
+            "paper-conference" => {
+                convert_conference_paper(id, e, options).map(Entry::InProceedings)
+            }
+            "report" => convert_report(id, e, options).map(Entry::Report),
+            "book" => convert_book(id, e, options).map(Entry::Book),
+            "software" => convert_software(id, e, options).map(Entry::Software),
+            "dataset" => convert_dataset(id, e, options).map(Entry::Dataset),
             ty => bail!("no BibLaTex entry type for CSL type {}", ty),
         }
     }
 
-    match_type(id, e).context(err_context)
+    unwrap_array_fields(&mut e);
+    let mut entry = match_type(id, &mut e, options).context(err_context)?;
+    if let Some(field) = &options.file_from {
+        *entry.file_mut() = e
+            .try_field_then(field, JsonValue::expect_string)?
+            .map(From::from);
+    }
+    keep_fields(&mut entry, &mut e, &options.keep_fields)?;
+    if let Err(err) = entry.validate(options.unknown_char_policy()) {
+        bail!("{:#}", err);
+    }
+    Ok(entry)
+}
+
+/// Copy the `--keep-fields` which were left unconverted in `e` into the entry's extra fields.
+fn keep_fields(entry: &mut Entry, e: &mut CslEntry, fields: &[KeepField]) -> Result<()> {
+    for f in fields {
+        let val = e.try_field_then(&f.csl, |v| match v {
+            JsonValue::String(s) => Ok(s),
+            JsonValue::Number(n) => Ok(n.to_string()),
+            JsonValue::Array(a) => a
+                .into_iter()
+                .map(JsonValue::expect_string)
+                .collect::<Result<Vec<_>>>()
+                .map(|a| a.join(", ")),
+            _ => bail!("only strings, numbers and lists of strings can be kept"),
+        })?;
+        if let Some(val) = val {
+            entry
+                .extra_fields_mut()
+                .insert(f.biblatex.clone(), val.into());
+        }
+    }
+    Ok(())
+}
+
+/// With `--crossref-proceedings`, add a `@proceedings` parent for each group of two or more
+/// conference papers sharing a booktitle and year.  Fields which every paper in the group agrees
+/// on are moved to the parent, and the papers get a `crossref` to it.  The parent's title is the
+/// booktitle, which BibLaTeX passes on to the papers as their booktitle, so the papers leave it
+/// out.  Parents are appended after all other entries, as BibTeX requires.
+pub fn crossref_proceedings(entries: &mut Vec<Entry>, options: &ConvertOptions) {
+    use std::collections::{BTreeMap, HashSet};
+
+    if !options.crossref_proceedings {
+        return;
+    }
+
+    let mut groups: BTreeMap<(String, types::Int), Vec<usize>> = BTreeMap::new();
+    for (i, e) in entries.iter().enumerate() {
+        if let Entry::InProceedings(p) = e {
+            if let Some(year) = p.year.number() {
+                groups
+                    .entry((p.book_title.0.clone(), year))
+                    .or_default()
+                    .push(i);
+            }
+        }
+    }
+
+    let mut taken: HashSet<String> = entries.iter().map(|e| e.id().to_string()).collect();
+    let mut parents = Vec::new();
+    for ((title, year), indices) in groups {
+        if indices.len() < 2 {
+            continue;
+        }
+        let key = proceedings_key(&title, year, &mut taken);
+        let mut children: Vec<_> = entries
+            .iter_mut()
+            .enumerate()
+            .filter(|(i, _)| indices.binary_search(i).is_ok())
+            .filter_map(|(_, e)| match e {
+                Entry::InProceedings(p) => Some(p),
+                _ => None,
+            })
+            .collect();
+
+        let mut parent = entry::Proceedings::new(key.clone(), title.into(), year.into());
+        macro_rules! move_shared {
+            ($($f:ident),*) => {$(
+                let first = &children[0].$f;
+                let shared = children.iter().all(|c| c.$f == *first);
+                if first.is_some() && shared {
+                    parent.$f = children[0].$f.take();
+                    for c in children.iter_mut() {
+                        c.$f = None;
+                    }
+                }
+            )*};
+        }
+        move_shared!(
+            editor,
+            event_date,
+            event_title,
+            isbn,
+            location,
+            organization,
+            publisher,
+            series,
+            venue,
+            volume,
+            volumes
+        );
+        for c in children {
+            c.crossref = Some(key.as_str().into());
+            c.book_title = String::new().into();
+        }
+        info!(key = %key, n = indices.len(), "grouped conference papers under @proceedings");
+        parents.push(Entry::Proceedings(parent));
+    }
+    entries.extend(parents);
+}
+
+/// A key for a `@proceedings` entry from the initials of the capitalised words of its title and
+/// its year, e.g. `IPCO2019`, which is not in `taken`.
+fn proceedings_key(
+    title: &str,
+    year: types::Int,
+    taken: &mut std::collections::HashSet<String>,
+) -> String {
+    let initials: String = title
+        .split_whitespace()
+        .filter_map(|w| w.chars().next())
+        .filter(char::is_ascii_uppercase)
+        .collect();
+    let base = if initials.is_empty() {
+        format!("proceedings{}", year)
+    } else {
+        format!("{}{}", initials, year)
+    };
+    let mut key = base.clone();
+    let mut n = 2;
+    while !taken.insert(key.clone()) {
+        key = format!("{}-{}", base, n);
+        n += 1;
+    }
+    key
 }
 
 #[cfg(test)]
@@ -371,16 +1504,194 @@ mod tests {
         let input: CslEntry = read_json(&path)?;
         path.set_extension("bib");
         let expected = std::fs::read_to_string(&path).context_read(&path)?;
-        let output = csl_to_biblatex(input)?;
+        let output = csl_to_biblatex(input, &ConvertOptions::default())?;
         assert_str_eq!(expected, output.biblatex().to_string());
         Ok(())
     }
 
+    /// Convert the input of the example `name`, after applying `edit` to it
+    fn convert_fixture(
+        name: &str,
+        edit: impl FnOnce(&mut CslEntry),
+        options: &ConvertOptions,
+    ) -> Result<String> {
+        let mut path =
+            Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/biblatex/")).join(name);
+        path.set_extension("json");
+        let mut input: CslEntry = read_json(&path)?;
+        edit(&mut input);
+        let output = csl_to_biblatex(input, options)?;
+        Ok(output
+            .biblatex()
+            .on_unknown(options.unknown_char_policy())
+            .to_string())
+    }
+
     #[test]
     fn parse_arxiv_category() -> Result<()> {
         use super::parse_arxiv_category as parse;
         assert_eq!(parse("Optimization and Control (math.OC)")?, "math.OC");
         assert_eq!(parse("Discrete Mathematics (cs.DM)")?, "cs.DM");
+        assert_eq!(
+            parse("Soft Condensed Matter (cond-mat.soft)")?,
+            "cond-mat.soft"
+        );
+        assert_eq!(parse("High Energy Physics - Theory (hep-th)")?, "hep-th");
+        assert!(parse("FOS: Mathematics").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn check_arxiv_category() {
+        use super::check_arxiv_category as check;
+        assert!(check("math.OC", true).is_ok());
+        assert!(check("mth.OC", false).is_ok());
+        assert!(check("mth.OC", true).is_err());
+    }
+
+    #[test]
+    fn parse_arxiv_id() -> Result<()> {
+        use super::parse_arxiv_id as parse;
+        let v = |s: &str| Some(s.to_string());
+        assert_eq!(
+            parse("https://arxiv.org/abs/1606.01935")?,
+            ("1606.01935".into(), None)
+        );
+        assert_eq!(
+            parse("https://arxiv.org/abs/1606.01935/")?,
+            ("1606.01935".into(), None)
+        );
+        assert_eq!(
+            parse("https://arxiv.org/abs/2103.12345v3?context=cs")?,
+            ("2103.12345".into(), v("3"))
+        );
+        assert_eq!(
+            parse("https://arxiv.org/abs/math.OC/0601001")?,
+            ("math.OC/0601001".into(), None)
+        );
+        assert_eq!(
+            parse("http://arxiv.org/abs/hep-th/9901001v2")?,
+            ("hep-th/9901001".into(), v("2"))
+        );
+        assert!(parse("https://arxiv.org/abs/").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn convert_page_range() -> Result<()> {
+        use super::convert_page_range as convert;
+        use serde_json::json;
+        assert_eq!(convert(json!("7"), true)?, types::Range::Single(7));
+        assert_eq!(
+            convert(json!("100-200"), true)?,
+            types::Range::Multi {
+                start: 100,
+                end: Some(200)
+            }
+        );
+        assert!(convert(json!("200-100"), false).is_ok());
+        assert!(convert(json!("200-100"), true).is_err());
+        assert_eq!(
+            convert(json!("100–"), true)?,
+            types::Range::Multi {
+                start: 100,
+                end: None
+            }
+        );
+        assert_eq!(
+            convert(json!("100 -"), true)?,
+            types::Range::Multi {
+                start: 100,
+                end: None
+            }
+        );
+        assert_eq!(convert(json!("-20"), true)?, types::Range::UpTo(20));
+        assert_eq!(convert(json!("–20"), true)?.biblatex().to_string(), "-20");
+        for invalid in ["-", "", "1-2-3", "99999999999"] {
+            assert!(convert(json!(invalid), false).is_err(), "{:?}", invalid);
+        }
+
+        let list = convert(json!("12, 15-18, 22,"), true)?;
+        assert_eq!(list.biblatex().to_string(), "12, 15-18, 22");
+        assert_eq!(
+            list,
+            types::Range::List(vec![
+                types::Range::Single(12),
+                types::Range::Multi {
+                    start: 15,
+                    end: Some(18)
+                },
+                types::Range::Single(22),
+            ])
+        );
+        assert_eq!(convert(json!("7,"), true)?, types::Range::Single(7));
+        assert!(convert(json!("12, 18-15"), true).is_err());
+        assert!(convert(json!("12, e5"), true).is_err());
+        assert!(convert(json!(","), true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn convert_name() -> Result<()> {
+        use serde_json::json;
+        let convert = |v| super::convert_name(v).map(|n| n.biblatex().to_string());
+        assert_eq!(
+            convert(json!({ "given": "Ellis L.", "family": "Johnson" }))?,
+            "Johnson, Ellis L."
+        );
+        assert_eq!(
+            convert(
+                json!({ "given": "Jan", "family": "Berg", "non-dropping-particle": "van der" })
+            )?,
+            "van der Berg, Jan"
+        );
+        assert_eq!(
+            convert(
+                json!({ "given": "Ludwig", "family": "Beethoven", "dropping-particle": "van" })
+            )?,
+            "van Beethoven, Ludwig"
+        );
+        assert_eq!(
+            convert(json!({ "given": "Ludwig van", "family": "Beethoven" }))?,
+            "van Beethoven, Ludwig"
+        );
+        assert_eq!(
+            convert(json!({
+                "given": "Charles Louis",
+                "family": "Vallée Poussin",
+                "dropping-particle": "de",
+                "non-dropping-particle": "la"
+            }))?,
+            r"de la Vall\'{e}e Poussin, Charles Louis"
+        );
+        assert_eq!(
+            convert(json!({ "given": "John Michael", "family": "Smith" }))?,
+            "Smith, John Michael"
+        );
+        assert_eq!(
+            convert(json!({ "given": "Martin Luther", "family": "King", "suffix": "Jr." }))?,
+            "King, Jr., Martin Luther"
+        );
+        assert_eq!(
+            convert(json!({ "literal": "European Commission" }))?,
+            "{European Commission}"
+        );
+        assert_eq!(
+            convert(json!({ "given": "Zedong", "family": "Mao", "static-ordering": true }))?,
+            "{Mao Zedong}"
+        );
+        assert_eq!(
+            convert(json!({ "given": "Zedong", "family": "Mao", "static-ordering": "false" }))?,
+            "Mao, Zedong"
+        );
+        assert_eq!(
+            convert(json!({ "given": "", "family": "World Health Organization" }))?,
+            "{World Health Organization}"
+        );
+        assert_eq!(
+            convert(json!({ "family": "World Health Organization" }))?,
+            "{World Health Organization}"
+        );
         Ok(())
     }
 
@@ -410,6 +1721,93 @@ mod tests {
             Date::year_month(2001, 1)
         );
         assert_eq!(convert(json!({ "raw": "2001"}))?, Date::year(2001));
+        assert_eq!(
+            convert(json!({ "literal": "Spring 2020"}))?,
+            Date::year_month(2020, 21)
+        );
+        assert_eq!(
+            convert(json!({ "date-parts": [[2020]], "season": 3 }))?,
+            Date::year_month(2020, 23)
+        );
+        assert_eq!(
+            convert(json!({ "date-parts": [[2020, 16]]}))?,
+            Date::year_month(2020, 24)
+        );
+        assert_eq!(
+            convert(json!({ "literal": "Forthcoming"}))?,
+            Date {
+                year: types::Year::Literal("Forthcoming".into()),
+                month: None,
+                day: None,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn season() -> Result<()> {
+        let edit = |e: &mut CslEntry| {
+            e.insert(
+                csl::ISSUED.into(),
+                serde_json::json!({ "literal": "Spring 2020" }),
+            );
+        };
+        let output = convert_fixture("article", edit, &ConvertOptions::default())?;
+        assert!(output.contains("    year = {2020},\n"));
+        assert!(output.contains("    date = {2020-21},\n"));
+        assert!(!output.contains("month"));
+        Ok(())
+    }
+
+    #[test]
+    fn literal_date_without_year() -> Result<()> {
+        let edit = |e: &mut CslEntry| {
+            e.insert(
+                csl::ISSUED.into(),
+                serde_json::json!({ "literal": "Forthcoming" }),
+            );
+        };
+        let output = convert_fixture("article", edit, &ConvertOptions::default())?;
+        assert!(output.contains("    year = {Forthcoming},\n"));
+        assert!(!output.contains("date"));
+        Ok(())
+    }
+
+    #[test]
+    fn backwards_page_range() -> Result<()> {
+        let edit = |e: &mut CslEntry| {
+            e.insert(csl::PAGE.into(), "200-100".into());
+        };
+        let output = convert_fixture("article", edit, &ConvertOptions::default())?;
+        assert!(output.contains("    pages = {200-100},\n"));
+        let strict = ConvertOptions {
+            strict_page_ranges: true,
+            ..ConvertOptions::default()
+        };
+        assert!(convert_fixture("article", edit, &strict).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn date_parts_roundtrip() -> Result<()> {
+        use super::convert_date as convert;
+        use serde_json::json;
+        for date in [
+            Date::full(2001, 1, 25),
+            Date::year_month(2001, 1),
+            Date::year(2001),
+            Date {
+                year: types::Year::Literal("In press".into()),
+                month: None,
+                day: None,
+            },
+        ] {
+            assert_eq!(convert(date.to_csl_date_parts())?, date);
+        }
+        assert_eq!(
+            Date::year_month(2001, 1).to_csl_date_parts(),
+            json!({ "date-parts": [[2001, 1]]})
+        );
         Ok(())
     }
 
@@ -452,4 +1850,553 @@ mod tests {
     fn book() -> Result<()> {
         check_output("book")
     }
+
+    #[test]
+    fn edited_book() -> Result<()> {
+        check_output("edited-book")
+    }
+
+    #[test]
+    fn software() -> Result<()> {
+        check_output("software")
+    }
+
+    #[test]
+    fn dataset() -> Result<()> {
+        check_output("dataset")
+    }
+
+    #[test]
+    fn month_macros() -> Result<()> {
+        let options = ConvertOptions {
+            month_macros: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            convert_month(Some(3), &options)
+                .unwrap()
+                .biblatex()
+                .to_string(),
+            "mar"
+        );
+        assert_eq!(
+            convert_month(Some(13), &options)
+                .unwrap()
+                .biblatex()
+                .to_string(),
+            "13"
+        );
+
+        let output = convert_fixture("article", |_| {}, &options)?;
+        assert!(output.contains("    month = jun,\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn conference_event() -> Result<()> {
+        use serde_json::json;
+        let paper = |event: JsonValue| -> Result<String> {
+            let mut input = json!({
+                "id": "smith2019",
+                "type": "paper-conference",
+                "title": "A paper",
+                "author": [{ "family": "Smith", "given": "Jane" }],
+                "issued": { "date-parts": [[2019]] },
+                "container-title": "Proceedings of the Conference",
+            })
+            .unwrap_object();
+            input.extend(event.unwrap_object());
+            let output = csl_to_biblatex(input, &ConvertOptions::default())?;
+            Ok(output.biblatex().to_string())
+        };
+
+        let output = paper(json!({
+            "event-title": "The Conference",
+            "event-place": "Auckland, New Zealand",
+            "event-date": { "date-parts": [[2019, 6, 10]] },
+        }))?;
+        assert!(output.contains("    booktitle = {Proceedings of the Conference},\n"));
+        assert!(output.contains("    eventtitle = {The Conference},\n"));
+        assert!(output.contains("    venue = {Auckland, New Zealand},\n"));
+        assert!(output.contains("    eventdate = {2019-06-10},\n"));
+
+        let output = paper(json!({
+            "event": { "name": "The Conference", "location": "Auckland" },
+        }))?;
+        assert!(output.contains("    eventtitle = {The Conference},\n"));
+        assert!(output.contains("    venue = {Auckland},\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn container_title_array() -> Result<()> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/biblatex/article.json");
+        let input: CslEntry = read_json(path)?;
+        let expected = csl_to_biblatex(input.clone(), &ConvertOptions::default())?;
+
+        let mut array_input = input.clone();
+        let title = array_input.remove(csl::CONTAINER_TITLE).unwrap();
+        array_input.insert(csl::CONTAINER_TITLE.into(), vec![title].into());
+        let output = csl_to_biblatex(array_input, &ConvertOptions::default())?;
+        assert_eq!(
+            output.biblatex().to_string(),
+            expected.biblatex().to_string()
+        );
+
+        let mut empty_input = input;
+        empty_input.insert(csl::CONTAINER_TITLE.into(), JsonValue::Array(vec![]));
+        let err = csl_to_biblatex(empty_input, &ConvertOptions::default()).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "entry `barnhart1998` is missing required CSL field(s): `container-title`"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn publisher_array() -> Result<()> {
+        let input = serde_json::json!({
+            "id": "knuth1997",
+            "type": "book",
+            "title": "The Art of Computer Programming",
+            "author": [{ "family": "Knuth", "given": "Donald E." }],
+            "issued": { "date-parts": [[1997]] },
+            "publisher": ["Addison-Wesley"],
+        });
+        let output = csl_to_biblatex(input.unwrap_object(), &ConvertOptions::default())?;
+        assert!(output
+            .biblatex()
+            .to_string()
+            .contains("    publisher = {Addison-Wesley},\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn missing_required_fields() -> Result<()> {
+        let mut input: CslEntry = read_json(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/biblatex/article.json"
+        ))?;
+        input.remove(csl::CONTAINER_TITLE);
+        input.remove(csl::ISSUED);
+        let err = csl_to_biblatex(input, &ConvertOptions::default()).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "entry `barnhart1998` is missing required CSL field(s): `container-title`, `issued`"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn keep_fields() -> Result<()> {
+        let options = ConvertOptions {
+            keep_fields: vec![
+                "publisher-place=location".parse()?,
+                "archive".parse()?,
+                "title".parse()?,
+            ],
+            ..Default::default()
+        };
+        let edit = |e: &mut CslEntry| {
+            e.insert(csl::PUBLISHER_PLACE.into(), "Linthicum, MD".into());
+            e.insert(csl::ARCHIVE.into(), "JSTOR".into());
+        };
+        let output = convert_fixture("article", edit, &options)?;
+        assert!(output.ends_with("    archive = {JSTOR},\n    location = {Linthicum, MD},\n}\n"));
+        assert_eq!(output.matches("    title = ").count(), 1);
+        assert!("=location".parse::<KeepField>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn biblatex_type_override() -> Result<()> {
+        use serde_json::json;
+        let options = ConvertOptions::default();
+        let convert = |custom: Option<JsonValue>| {
+            let edit = |e: &mut CslEntry| {
+                e.insert(csl::TYPE.into(), "article-journal".into());
+                if let Some(custom) = custom {
+                    e.insert(csl::CUSTOM.into(), custom);
+                }
+            };
+            convert_fixture("conference-paper", edit, &options)
+        };
+        assert!(convert(None)?.starts_with("@article{"));
+        let custom = json!({ "biblatex-type": "InProceedings" });
+        assert!(convert(Some(custom))?.starts_with("@inproceedings{"));
+        assert!(convert(Some(json!({ "biblatex-type": "patent" }))).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn editor_without_author() -> Result<()> {
+        let options = ConvertOptions::default();
+        let edit = |e: &mut CslEntry| {
+            e.insert(csl::AUTHOR.into(), serde_json::json!([]));
+        };
+        let output = convert_fixture("edited-book", edit, &options)?;
+        assert!(!output.contains("author"));
+        let edit = |e: &mut CslEntry| {
+            e.remove(csl::AUTHOR);
+        };
+        assert!(convert_fixture("book", edit, &options).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn max_authors() -> Result<()> {
+        let convert = |max| {
+            let options = ConvertOptions {
+                max_authors: NonZeroUsize::new(max),
+                ..Default::default()
+            };
+            convert_fixture("article", |_| {}, &options)
+        };
+        assert!(convert(2)?
+            .contains("    author = {Barnhart, Cynthia and Johnson, Ellis L. and others},\n"));
+        assert!(convert(5)?.contains(" and Vance, Pamela H.},\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn article_number() -> Result<()> {
+        let options = ConvertOptions::default();
+        let with_number = |e: &mut CslEntry| {
+            e.insert(csl::NUMBER.into(), "e0251234".into());
+        };
+        let output = convert_fixture("article", with_number, &options)?;
+        assert!(!output.contains("eid"));
+
+        let without_pages = |e: &mut CslEntry| {
+            with_number(e);
+            e.remove(csl::PAGE);
+        };
+        let output = convert_fixture("article", without_pages, &options)?;
+        assert!(output.contains("    eid = {e0251234},\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn number_per_type() -> Result<()> {
+        let options = ConvertOptions::default();
+        let edit = |e: &mut CslEntry| {
+            e.insert(csl::ISSUE.into(), "4".into());
+            e.insert(csl::NUMBER.into(), "e0251234".into());
+            e.remove(csl::PAGE);
+        };
+        let output = convert_fixture("article", edit, &options)?;
+        assert!(output.contains("    number = {4},\n"));
+        assert!(output.contains("    eid = {e0251234},\n"));
+
+        for name in ["tech-report", "working-paper"] {
+            let edit = |e: &mut CslEntry| {
+                e.insert(csl::NUMBER.into(), 17.into());
+                e.insert(csl::ISSUE.into(), "3".into());
+            };
+            let output = convert_fixture(name, edit, &options)?;
+            assert!(output.contains("    number = {17},\n"), "{}", output);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn note() -> Result<()> {
+        let convert = |strip_note_metadata| {
+            let options = ConvertOptions {
+                strip_note_metadata,
+                ..Default::default()
+            };
+            let edit = |e: &mut CslEntry| {
+                e.insert(
+                    csl::NOTE.into(),
+                    "Published as: Chapter 3\nDOI: 10.1000/xyz\nPMID: 12345".into(),
+                );
+            };
+            convert_fixture("article", edit, &options)
+        };
+        assert!(convert(false)?.contains("PMID: 12345},\n"));
+        assert!(convert(true)?.contains("    note = {Published as: Chapter 3},\n"));
+
+        assert_eq!(
+            strip_note_metadata("Reprinted {:original-date: 1900} in 2000.\n original-title: A\n"),
+            "Reprinted in 2000."
+        );
+        assert_eq!(strip_note_metadata("tex.key: x\nissued: 2000"), "");
+        assert_eq!(
+            strip_note_metadata("Note: retracted in 2021\nSee: the erratum\nISBN: 0-201-89683-4"),
+            "Note: retracted in 2021\nSee: the erratum"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn file_from() -> Result<()> {
+        let edit = |e: &mut CslEntry| {
+            e.insert("attachment".into(), "/home/me/papers/a_b%c.pdf".into());
+        };
+        let options = ConvertOptions {
+            file_from: Some("attachment".into()),
+            ..Default::default()
+        };
+        let output = convert_fixture("article", edit, &options)?;
+        assert!(output.contains("    file = {/home/me/papers/a_b%c.pdf},\n"));
+        let output = convert_fixture("article", edit, &ConvertOptions::default())?;
+        assert!(!output.contains("file = "));
+        Ok(())
+    }
+
+    #[test]
+    fn original() -> Result<()> {
+        let edit = |e: &mut CslEntry| {
+            e.insert(
+                csl::ORIGINAL_DATE.into(),
+                serde_json::json!({ "date-parts": [[1687, 7, 5]] }),
+            );
+            e.insert(
+                csl::ORIGINAL_TITLE.into(),
+                serde_json::json!(["Philosophiae Naturalis Principia Mathematica"]),
+            );
+        };
+        let output = convert_fixture("book", edit, &ConvertOptions::default())?;
+        assert!(output.contains(
+            "    origdate = {1687-07-05},\n    \
+             origtitle = {Philosophiae Naturalis Principia Mathematica},\n"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn series() -> Result<()> {
+        let edit = |e: &mut CslEntry| {
+            e.insert(
+                csl::COLLECTION_TITLE.into(),
+                "Lecture Notes in Computer Science".into(),
+            );
+            e.insert(csl::COLLECTION_NUMBER.into(), 12345.into());
+            e.insert(csl::PART.into(), "II".into());
+            e.insert(csl::NUMBER_OF_VOLUMES.into(), "3".into());
+        };
+        let output = convert_fixture("conference-paper", edit, &ConvertOptions::default())?;
+        assert!(output.ends_with(
+            "    number = {12345},\n    part = {II},\n    publisher = {ACM Press},\n    \
+             series = {Lecture Notes in Computer Science},\n    volumes = {3},\n}\n"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn crossref_proceedings() -> Result<()> {
+        let options = ConvertOptions {
+            crossref_proceedings: true,
+            ..Default::default()
+        };
+        let input: CslEntry = read_json(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/biblatex/conference-paper.json"
+        ))?;
+        let mut entries = Vec::new();
+        for id in ["a", "b", "c"] {
+            let mut e = input.clone();
+            e.insert(csl::ID.into(), id.into());
+            if id == "c" {
+                e.insert(
+                    csl::ISSUED.into(),
+                    serde_json::json!({ "date-parts": [[1960]] }),
+                );
+            }
+            entries.push(csl_to_biblatex(e, &options)?);
+        }
+        super::crossref_proceedings(&mut entries, &options);
+
+        let output: Vec<_> = entries.iter().map(|e| e.biblatex().to_string()).collect();
+        assert_eq!(output.len(), 4);
+        assert!(output[0].contains("    crossref = {PDII1959},\n"));
+        assert!(!output[0].contains("publisher"));
+        assert!(!output[0].contains("booktitle"));
+        assert!(entries[0].validate(UnknownCharPolicy::default()).is_ok());
+        assert!(!output[2].contains("crossref"));
+        assert!(output[2].contains("    booktitle = {"));
+        assert!(output[3].starts_with("@proceedings{PDII1959,\n"));
+        assert!(output[3].contains("    title = {Papers presented at the December 1-3, 1959, "));
+        assert!(output[3].contains("    publisher = {ACM Press},\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn abbreviate_journal() -> Result<()> {
+        let options = ConvertOptions {
+            abbreviate_journal: true,
+            ..Default::default()
+        };
+        let output = convert_fixture("article", |_| {}, &options)?;
+        assert!(output.contains("    journaltitle = {Oper. Res.},\n"));
+
+        let edit = |e: &mut CslEntry| {
+            e.insert(
+                csl::CONTAINER_TITLE.into(),
+                "Journal of Made Up Results".into(),
+            );
+            e.insert(csl::CONTAINER_TITLE_SHORT.into(), "J. Made Up Res.".into());
+        };
+        let output = convert_fixture("article", edit, &options)?;
+        assert!(output.contains("    journaltitle = {J. Made Up Res.},\n"));
+        assert!(!output.contains("shortjournal"));
+
+        let output = convert_fixture("article", edit, &ConvertOptions::default())?;
+        assert!(output.contains("    journaltitle = {Journal of Made Up Results},\n"));
+        assert!(output.contains("    shortjournal = {J. Made Up Res.},\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn fingerprint() -> Result<()> {
+        let default = ConvertOptions::default().fingerprint()?;
+        assert_eq!(default, ConvertOptions::default().fingerprint()?);
+        let changed = [
+            ConvertOptions {
+                month_macros: true,
+                ..Default::default()
+            },
+            ConvertOptions {
+                unknown_chars: UnknownChars::Passthrough,
+                ..Default::default()
+            },
+        ];
+        for options in changed {
+            assert_ne!(options.fingerprint()?, default);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_chars() -> Result<()> {
+        let edit = |e: &mut CslEntry| {
+            e.insert(csl::TITLE.into(), "A study of 漢字".into());
+        };
+        assert!(convert_fixture("article", edit, &ConvertOptions::default()).is_err());
+        for (policy, title) in [
+            (UnknownChars::Passthrough, "A study of 漢字"),
+            (UnknownChars::Substitute, "A study of ??"),
+        ] {
+            let options = ConvertOptions {
+                unknown_chars: policy,
+                ..Default::default()
+            };
+            let output = convert_fixture("article", edit, &options)?;
+            assert!(output.contains(&format!("    title = {{{}}},\n", title)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn abstract_() -> Result<()> {
+        let edit = |e: &mut CslEntry| {
+            e.insert(
+                csl::ABSTRACT.into(),
+                "<jats:title>Abstract</jats:title><jats:p>We solve 100% of\n  instances &amp; more.</jats:p>"
+                    .into(),
+            );
+        };
+        let output = convert_fixture("article", edit, &ConvertOptions::default())?;
+        assert!(
+            output.contains("    abstract = {Abstract We solve 100\\% of instances \\& more.},\n")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn keywords() -> Result<()> {
+        let edit = |e: &mut CslEntry| {
+            e.insert(
+                csl::KEYWORD.into(),
+                "column generation, integer programming,".into(),
+            );
+            e.insert(
+                csl::CATEGORIES.into(),
+                serde_json::json!(["Integer programming", "R&D"]),
+            );
+        };
+        let output = convert_fixture("article", edit, &ConvertOptions::default())?;
+        assert!(output.contains(
+            "    keywords = {column generation, integer programming, Integer programming, R\\&D},\n"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn babel_language() {
+        use super::babel_language as babel;
+        assert_eq!(babel("en-US"), Some("american"));
+        assert_eq!(babel("en"), Some("english"));
+        assert_eq!(babel("de_DE"), Some("ngerman"));
+        assert_eq!(babel("de-CH"), Some("nswissgerman"));
+        assert_eq!(babel("pt-PT"), Some("portuguese"));
+        assert_eq!(babel("French"), Some("french"));
+        assert_eq!(babel("tlh"), None);
+    }
+
+    #[test]
+    fn biorxiv() -> Result<()> {
+        check_output("biorxiv")
+    }
+
+    #[test]
+    fn preprint_server() {
+        use PreprintServer::*;
+        let ssrn = PreprintServer::detect(None, Some("10.2139/ssrn.3456789"), Some("Elsevier BV"));
+        assert_eq!(ssrn, Some(Ssrn));
+        assert_eq!(
+            Ssrn.eprint_id(None, Some("10.2139/ssrn.3456789"))
+                .as_deref(),
+            Some("3456789")
+        );
+        let url = "https://osf.io/preprints/psyarxiv/abc12";
+        assert_eq!(PreprintServer::detect(Some(url), None, None), Some(Osf));
+        assert_eq!(Osf.eprint_id(Some(url), None).as_deref(), Some("abc12"));
+        assert_eq!(
+            PreprintServer::detect(Some("https://example.com/paper"), None, Some("Foo")),
+            None
+        );
+        for (url, doi, publisher) in [
+            (Some("https://arxiv.org/abs/2101.00001"), None, None),
+            (None, Some("10.48550/arXiv.2101.00001"), None),
+            (None, None, Some("arXiv")),
+        ] {
+            assert_eq!(PreprintServer::detect(url, doi, publisher), Some(Arxiv));
+        }
+        assert_eq!(
+            PreprintServer::detect(
+                Some("https://eartharxiv.org/repository/view/1234/"),
+                None,
+                None
+            ),
+            None
+        );
+
+        let ssrn = "https://papers.ssrn.com/sol3/papers.cfm?abstract_id=1234567";
+        assert_eq!(Ssrn.eprint_id(Some(ssrn), None).as_deref(), Some("1234567"));
+        for url in [
+            "https://ideas.repec.org/p/nbr/nberwo/12345.html",
+            "https://econpapers.repec.org/RePEc:nbr:nberwo:12345",
+        ] {
+            assert_eq!(PreprintServer::detect(Some(url), None, None), Some(Repec));
+            assert_eq!(
+                Repec.eprint_id(Some(url), None).as_deref(),
+                Some("nbr:nberwo:12345")
+            );
+        }
+    }
+
+    #[test]
+    fn working_paper_eprint() -> Result<()> {
+        let edit = |e: &mut CslEntry| {
+            e.insert(
+                csl::URL.into(),
+                "https://papers.ssrn.com/sol3/papers.cfm?abstract_id=1234567".into(),
+            );
+        };
+        let output = convert_fixture("working-paper", edit, &ConvertOptions::default())?;
+        assert!(output.contains("    eprint = {1234567},\n    eprinttype = {SSRN},\n"));
+        Ok(())
+    }
 }
@@ -25,6 +25,7 @@ pub const CONTAINER_TITLE_SHORT: &str = "container-title-short";
 pub const CONTAINER_TITLE: &str = "container-title";
 pub const CONTRIBUTOR: &str = "contributor";
 pub const CURATOR: &str = "curator";
+pub const CUSTOM: &str = "custom";
 pub const DIMENSIONS: &str = "dimensions";
 pub const DIRECTOR: &str = "director";
 pub const DIVISION: &str = "division";
@@ -100,3 +101,110 @@ pub const VOLUME_TITLE_SHORT: &str = "volume-title-short";
 pub const VOLUME_TITLE: &str = "volume-title";
 pub const VOLUME: &str = "volume";
 pub const YEAR_SUFFIX: &str = "year-suffix";
+
+/// Every CSL field above
+pub const ALL: &[&str] = &[
+    ABSTRACT,
+    ACCESSED,
+    ANNOTE,
+    ARCHIVE_COLLECTION,
+    ARCHIVE_LOCATION,
+    ARCHIVE_PLACE,
+    ARCHIVE,
+    AUTHOR,
+    AUTHORITY,
+    AVAILABLE_DATE,
+    CALL_NUMBER,
+    CATEGORIES,
+    CHAIR,
+    CHAPTER_NUMBER,
+    CITATION_KEY,
+    CITATION_LABEL,
+    CITATION_NUMBER,
+    COLLECTION_EDITOR,
+    COLLECTION_NUMBER,
+    COLLECTION_TITLE,
+    COMPILER,
+    COMPOSER,
+    CONTAINER_AUTHOR,
+    CONTAINER_TITLE_SHORT,
+    CONTAINER_TITLE,
+    CONTRIBUTOR,
+    CURATOR,
+    CUSTOM,
+    DIMENSIONS,
+    DIRECTOR,
+    DIVISION,
+    DOI,
+    EDITION,
+    EDITOR,
+    EDITORIAL_DIRECTOR,
+    EVENT_DATE,
+    EVENT_PLACE,
+    EVENT_TITLE,
+    EVENT,
+    EXECUTIVE_PRODUCER,
+    FIRST_REFERENCE_NOTE_NUMBER,
+    GENRE,
+    GUEST,
+    HOST,
+    ID,
+    ILLUSTRATOR,
+    INTERVIEWER,
+    ISBN,
+    ISSN,
+    ISSUE,
+    ISSUED,
+    JOURNAL_ABBREVIATION,
+    JURISDICTION,
+    KEYWORD,
+    LANGUAGE,
+    LOCATOR,
+    MEDIUM,
+    NARRATOR,
+    NOTE,
+    NUMBER_OF_PAGES,
+    NUMBER_OF_VOLUMES,
+    NUMBER,
+    ORGANIZER,
+    ORIGINAL_AUTHOR,
+    ORIGINAL_DATE,
+    ORIGINAL_PUBLISHER_PLACE,
+    ORIGINAL_PUBLISHER,
+    ORIGINAL_TITLE,
+    PAGE_FIRST,
+    PAGE,
+    PART_TITLE,
+    PART,
+    PERFORMER,
+    PMCID,
+    PMID,
+    PRINTING,
+    PRODUCER,
+    PUBLISHER_PLACE,
+    PUBLISHER,
+    RECIPIENT,
+    REFERENCES,
+    REVIEWED_AUTHOR,
+    REVIEWED_GENRE,
+    REVIEWED_TITLE,
+    SCALE,
+    SCRIPT_WRITER,
+    SECTION,
+    SERIES_CREATOR,
+    SHORTTITLE,
+    SOURCE,
+    STATUS,
+    SUBMITTED,
+    SUPPLEMENT,
+    TITLE_SHORT,
+    TITLE,
+    TRANSLATOR,
+    TYPE,
+    URL,
+    VERSION,
+    VOLUME_TITLE_SHORT,
+    VOLUME_TITLE,
+    VOLUME,
+    YEAR_SUFFIX,
+];
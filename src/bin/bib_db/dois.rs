@@ -0,0 +1,166 @@
+//! Plain text lists of DOIs as `fetch` input.  Each DOI becomes a stub entry holding only the
+//! DOI, which is filled in by the usual fetch, and then given a key made from its first author,
+//! year and title.
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::Path;
+
+use super::csl_fields as csl;
+use super::*;
+use regex::Regex;
+use unicode_normalization::UnicodeNormalization;
+
+/// Words skipped when choosing the title word of a key
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "by", "for", "from", "in", "of", "on", "the", "to", "with",
+];
+
+/// Whether `path` is a list of DOIs rather than a CSL database, judging by its extension
+pub fn is_doi_list(path: &Path) -> bool {
+    matches!(path.extension(), Some(ext) if ext == "txt")
+}
+
+/// Stub entries for the DOIs listed in `paths`, one per line.  Blank lines and lines starting
+/// with `#` are skipped, and anything around the DOI (such as `https://doi.org/`) is ignored.  The
+/// DOI is stored in `doi_field` and used as the entry's `id` until [`assign_keys`] is called.
+pub fn read_doi_lists(paths: &[PathBuf], doi_field: &str) -> Result<Vec<CslEntry>> {
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for path in paths {
+        let count = entries.len();
+        let text = if is_stdio(path) {
+            let mut s = String::new();
+            std::io::stdin().read_to_string(&mut s)?;
+            s
+        } else {
+            std::fs::read_to_string(path).context_read(path)?
+        };
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let doi = match fetch::DOI_IN_TEXT.find(line) {
+                Some(m) => m.as_str().trim_end_matches(['.', ',', ';']),
+                None => bail!("no DOI on line {} of {}: {}", i + 1, path.display(), line),
+            };
+            if !seen.insert(cache::normalize_doi(doi)) {
+                warn!(doi, "skipping duplicate DOI");
+                continue;
+            }
+            let mut entry = CslEntry::new();
+            entry.insert(csl::ID.into(), doi.into());
+            entry.insert(doi_field.into(), doi.into());
+            entries.push(entry);
+        }
+        info!(path=%path.display(), n_entries = entries.len() - count, "read DOI list");
+    }
+    Ok(entries)
+}
+
+/// Lowercase ASCII letters and digits of `s`, with accents removed
+fn ascii_word(s: &str) -> String {
+    s.nfkd()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// A key like `smith2020travelling`: the first author's (or editor's) family name, the year, and
+/// the first word of the title which isn't a stop word.  `None` if any part is missing.
+fn key_stem(entry: &CslEntry) -> Option<String> {
+    lazy_static! {
+        static ref TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+    }
+    let name = [csl::AUTHOR, csl::EDITOR]
+        .iter()
+        .filter_map(|f| entry.get(*f)?.as_array()?.first())
+        .find_map(|n| n.get("family").or_else(|| n.get("literal"))?.as_str())?;
+    let name = Some(ascii_word(name.split_whitespace().last()?)).filter(|n| !n.is_empty())?;
+    let year = convert::issued_year(entry).ok()??;
+    let title = entry.get(csl::TITLE)?.as_str()?;
+    let title = TAG.replace_all(title, " ");
+    let word = title
+        .split(|c: char| !c.is_alphanumeric())
+        .map(ascii_word)
+        .find(|w| !w.is_empty() && !STOP_WORDS.contains(&w.as_str()))?;
+    Some(format!("{}{}{}", name, year, word))
+}
+
+/// Replace the DOIs used as the ids of `entries` with keys made by [`key_stem`].  Clashes with
+/// each other and with `taken` are resolved by appending `a`, `b`, ...  Entries without enough
+/// metadata for a key keep their DOI.
+pub fn assign_keys(entries: &mut [CslEntry], mut taken: HashSet<String>) {
+    for e in entries {
+        let stem = match key_stem(e) {
+            Some(stem) => stem,
+            None => {
+                warn!(id = ?e.get(csl::ID), "not enough metadata for a key, using the DOI");
+                continue;
+            }
+        };
+        let key = std::iter::once(stem.clone())
+            .chain(('a'..='z').map(|c| format!("{}{}", stem, c)))
+            .find(|k| !taken.contains(k))
+            .unwrap_or_else(|| format!("{}-{}", stem, taken.len()));
+        taken.insert(key.clone());
+        e.insert(csl::ID.into(), key.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keys() {
+        let entry = |family: &str, title: &str| {
+            json!({
+                "id": "10.1000/xyz",
+                "author": [{ "family": family, "given": "A." }],
+                "issued": { "date-parts": [[2020, 3]] },
+                "title": title,
+            })
+            .unwrap_object()
+        };
+        let mut entries = vec![
+            entry("Gödel", "On <i>Formally</i> Undecidable Propositions"),
+            entry("van der Berg", "The Travelling Salesman"),
+            entry("Berg", "Travelling salesmen"),
+            CslEntry::from_iter([("id".to_string(), "10.1000/abc".into())]),
+        ];
+        assign_keys(
+            &mut entries,
+            HashSet::from(["berg2020travelling".to_string()]),
+        );
+        let ids: Vec<_> = entries.iter().map(|e| e["id"].as_str().unwrap()).collect();
+        assert_eq!(
+            ids,
+            [
+                "godel2020formally",
+                "berg2020travellinga",
+                "berg2020travellingb",
+                "10.1000/abc"
+            ]
+        );
+    }
+
+    #[test]
+    fn doi_list() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("dois.txt");
+        std::fs::write(
+            &path,
+            "# my DOIs\n10.1000/ABC\n\nhttps://doi.org/10.1000/xyz.\n10.1000/abc\n",
+        )?;
+        let entries = read_doi_lists(std::slice::from_ref(&path), csl::DOI);
+        let dois: Vec<_> = entries?
+            .iter()
+            .map(|e| e[csl::DOI].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(dois, ["10.1000/ABC", "10.1000/xyz"]);
+        assert!(is_doi_list(&path));
+        Ok(())
+    }
+}
@@ -50,6 +50,89 @@ pub struct ClArgs {
     show_bib: bool,
 }
 
+/// Convert every example and check the output matches its BibLaTeX, to confirm a build works
+pub fn self_test() -> Result<()> {
+    use tex_tools::biblatex::ToBiblatex;
+
+    let mut failed = 0;
+    for e in EXAMPLES {
+        let _s = error_span!("example", name = e.name).entered();
+        let result = serde_json::from_str(e.json)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| convert::csl_to_biblatex(json, &Default::default()));
+        match result {
+            Ok(output) if output.biblatex().to_string() == e.bib => {
+                info!("ok");
+            }
+            Ok(output) => {
+                error!(
+                    "output does not match\n--- expected\n{}--- got\n{}",
+                    e.bib,
+                    output.biblatex()
+                );
+                failed += 1;
+            }
+            Err(err) => {
+                error!("{:#}", err);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} examples failed", failed, EXAMPLES.len());
+    }
+    info!(count = EXAMPLES.len(), "all examples converted correctly");
+    Ok(())
+}
+
+#[derive(Args)]
+pub struct BlessArgs {
+    /// Directory of examples, such as tests/biblatex
+    dir: PathBuf,
+}
+
+/// Convert each `.json` example in a directory and write the output to the `.bib` beside it, to
+/// update the expected BibLaTeX after an intentional change to the output.  New examples only need
+/// the `.json`.  The examples are built into the binary, so rebuild before running `self-test`.
+pub fn bless(args: BlessArgs) -> Result<()> {
+    use tex_tools::biblatex::ToBiblatex;
+
+    let mut paths = Vec::new();
+    for f in std::fs::read_dir(&args.dir).context_read(&args.dir)? {
+        let path = f.context_read(&args.dir)?.path();
+        if matches!(path.extension(), Some(ext) if ext == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut failed = 0;
+    let mut count_updated = 0;
+    for json in &paths {
+        let _s = error_span!("example", path = %json.display()).entered();
+        let output =
+            match read_json(json).and_then(|e| convert::csl_to_biblatex(e, &Default::default())) {
+                Ok(output) => output.biblatex().to_string(),
+                Err(err) => {
+                    error!("{:#}", err);
+                    failed += 1;
+                    continue;
+                }
+            };
+        let bib = json.with_extension("bib");
+        if std::fs::read_to_string(&bib).ok().as_ref() != Some(&output) {
+            std::fs::write(&bib, output).context_write(&bib)?;
+            info!(path = %bib.display(), "updated");
+            count_updated += 1;
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} examples failed to convert", failed, paths.len());
+    }
+    info!(count = paths.len(), count_updated, "blessed examples");
+    Ok(())
+}
+
 pub fn main(args: ClArgs) -> Result<()> {
     let e = EXAMPLES
         .iter()
@@ -63,3 +146,22 @@ pub fn main(args: ClArgs) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bless_matches_examples() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let e = EXAMPLES.iter().find(|e| e.name == "article").unwrap();
+        std::fs::write(dir.path().join("article.json"), e.json)?;
+        std::fs::write(dir.path().join("article.bib"), "outdated")?;
+        bless(BlessArgs {
+            dir: dir.path().to_path_buf(),
+        })?;
+        let bib = std::fs::read_to_string(dir.path().join("article.bib"))?;
+        assert_eq!(bib, e.bib);
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::time::Duration;
 use std::{num::NonZeroU32, path::Path};
 
@@ -6,8 +8,11 @@ use super::*;
 
 use futures::future;
 use governor as gv;
+use jsonschema::{error::ValidationErrorKind, ValidationError};
 use reqwest::{header, Client, Response};
-use tex_tools::biblatex::ToBiblatex;
+use tex_tools::crossref::{
+    clean_crossref_entry_with, collapse_arrays, strip_doi_url, suspicious_title, DROP_FIELDS,
+};
 
 type RateLimiter = gv::RateLimiter<
     gv::state::NotKeyed,
@@ -16,161 +21,71 @@ type RateLimiter = gv::RateLimiter<
     gv::middleware::NoOpMiddleware,
 >;
 
-fn pop_print_isbn(entry: &mut CslEntry) -> Option<JsonValue> {
-    let kinds = match entry.remove("isbn-type")? {
-        JsonValue::Array(a) => a,
-        _ => return None,
-    };
-
-    for val in kinds {
-        let mut val = match val {
-            JsonValue::Object(m) => m,
-            _ => continue,
-        };
-
-        if val.get("type").map(JsonValue::as_str).flatten() == Some("print") {
-            return val.remove("value");
-        }
-    }
-    None
+#[derive(Args, Debug, Clone, Default)]
+pub struct CleanOptions {
+    /// Keep a field in newly fetched entries which is dropped by default, such as `abstract` or
+    /// `funder`.  Fields outside the CSL schema are not validated.
+    #[clap(long, value_name = "FIELD", use_value_delimiter = true)]
+    keep: Vec<String>,
+
+    /// Drop a field from newly fetched entries, in addition to the defaults.
+    #[clap(long, value_name = "FIELD", use_value_delimiter = true)]
+    drop: Vec<String>,
+
+    /// Drop the `URL` of newly fetched entries when it only points to the DOI, as in
+    /// `http://dx.doi.org/10...`.  Other URLs are kept.
+    #[clap(long)]
+    strip_doi_url: bool,
+
+    /// Keep only the first value of these fields in newly fetched entries when they are arrays,
+    /// in addition to the fields which CSL defines as single values (such as `ISSN` and
+    /// `publisher`).
+    #[clap(long, value_name = "FIELD", use_value_delimiter = true)]
+    first_only: Vec<String>,
 }
 
-fn field_value_hacks(entry: &mut serde_json::Map<String, JsonValue>) {
-    for field in [csl::CONTAINER_TITLE, csl::CONTAINER_TITLE_SHORT] {
-        if let Some(JsonValue::String(s)) = entry.get_mut(field) {
-            if s.contains("&amp;") {
-                let new = s.replace("&amp;", "&");
-                *s = new;
-            }
-        }
+impl CleanOptions {
+    fn keeps(&self, field: &str) -> bool {
+        self.keep.iter().any(|f| f == field)
     }
-}
 
-#[instrument(level = "error", name = "clean", skip(entry))]
-fn clean_json(entry: &mut JsonValue) {
-    let entry = match entry.as_object_mut() {
-        Some(e) => e,
-        None => return,
-    };
-
-    if let Some(isbn) = pop_print_isbn(entry) {
-        entry.insert("ISBN".into(), isbn);
-    }
-
-    for drop_field in [
-        "abstract",
-        "alternative-id",
-        "article-number",
-        "assertion",
-        "content-domain",
-        "copywrite",
-        "copyright",
-        "created",
-        "deposited",
-        "funder",
-        "indexed",
-        "is-referenced-by-count",
-        "ISSN",
-        "journal-issue",
-        "license",
-        "link",
-        "member",
-        "prefix",
-        "published-online",
-        "published-print",
-        "published",
-        "publisher-location",
-        "reference-count",
-        "reference",
-        "references-count",
-        "relation",
-        "resource",
-        "score",
-        "short-title",
-        "subject",
-        "subtitle",
-        "update-policy",
-    ] {
-        entry.remove(drop_field);
-    }
-
-    for drop_if_empty in ["original-title"] {
-        let v = match entry.get(drop_if_empty) {
-            Some(v) => v,
-            None => continue,
-        };
-
-        let drop = match v {
-            JsonValue::Array(arr) => arr.is_empty(),
-            JsonValue::Object(map) => map.is_empty(),
-            JsonValue::Null => true,
-            _ => false,
-        };
-
-        if drop {
-            entry.remove(drop_if_empty);
-        }
+    fn drops(&self, field: &str) -> bool {
+        (DROP_FIELDS.contains(&field) && !self.keeps(field)) || self.drop.iter().any(|f| f == field)
     }
 
-    let ty = entry.remove_entry("type");
-    if let Some((ty_field, ty)) = ty {
-        let mut new_ty = None;
-
-        if let Some(ty) = ty.as_str() {
-            match ty {
-                "book-chapter" => {
-                    // You'd think this would be a book, but for some reason Crosscite and gang
-                    // like listing conference papers as book-chapters.
-                    new_ty = Some("paper-conference");
-                }
-                "journal-article" => {
-                    new_ty = Some("article-journal");
-                }
-                "proceedings-article" => {
-                    new_ty = Some("paper-conference");
-                }
-                _ => {}
+    /// Ignore schema errors for fields outside the CSL schema which the user asked to keep.
+    fn ignore_kept(&self, e: &ValidationError) -> bool {
+        match &e.kind {
+            ValidationErrorKind::AdditionalProperties { unexpected } => {
+                unexpected.iter().all(|f| self.keeps(f))
             }
-        }
-
-        if let Some(new_ty) = new_ty {
-            warn!(invalid=%ty, inferred=%new_ty, "converting out-of-spec type, inferred type may be wrong");
-            entry.insert(ty_field, new_ty.into());
-        } else {
-            entry.insert(ty_field, ty);
-        }
-    };
-
-    if entry.get("type").map(JsonValue::as_str).flatten() == Some("book-chapter") {
-        entry.insert("type".into(), "book".into());
-    }
-
-    if let Some(authors) = entry.get_mut("author").map(|a| a.as_array_mut()).flatten() {
-        for author in authors {
-            clean_name_fields(author);
-        }
-    }
-
-    if entry.get(csl::PUBLISHER).map(JsonValue::as_str).flatten() == Some("arXiv") {
-        if !entry.contains_key(csl::GENRE) {
-            entry.insert("genre".into(), "arxiv".into());
+            _ => false,
         }
     }
-
-    field_value_hacks(entry);
 }
 
-fn clean_name_fields(author: &mut JsonValue) {
-    if let Some(fields) = author.as_object_mut() {
-        for drop_field in ["ORCID", "authenticated-orcid", "sequence", "affiliation"] {
-            fields.remove(drop_field);
-        }
+fn clean_json(entry: &mut JsonValue, options: &CleanOptions) {
+    clean_crossref_entry_with(entry, |f| options.drops(f));
+    if let Some(entry) = entry.as_object_mut() {
+        collapse_arrays(entry, options.first_only.iter().map(String::as_str));
+    }
+    if options.strip_doi_url {
+        strip_doi_url(entry);
     }
 }
 
-#[instrument(level = "error", name = "fetch", skip(client, rl), fields(doi=%doi, url))]
-async fn fetch_one<'a>(client: &Client, rl: &RateLimiter, doi: &'a str) -> Option<JsonValue> {
-    let url = format!("https://doi.org/{}", urlencoding::Encoded(doi));
+#[instrument(level = "error", name = "fetch", skip(client, rl, resolver), fields(doi=%doi, url))]
+async fn fetch_one<'a>(
+    client: &Client,
+    rl: &RateLimiter,
+    resolver: &str,
+    doi: &'a str,
+) -> Option<JsonValue> {
+    let url = format!(
+        "{}/{}",
+        resolver.trim_end_matches('/'),
+        urlencoding::Encoded(doi)
+    );
     tracing::span::Span::current().record("url", &tracing::field::display(&url));
 
     rl.until_ready_with_jitter(gv::Jitter::up_to(Duration::from_millis(200)))
@@ -186,6 +101,8 @@ async fn fetch_one<'a>(client: &Client, rl: &RateLimiter, doi: &'a str) -> Optio
         Err(err) => {
             if let Some(status) = err.status() {
                 error!(%status, %err, "HTTP error")
+            } else if err.is_timeout() {
+                error!(%err, "request timed out")
             } else {
                 error!(%err, "Failed to send request")
             }
@@ -202,40 +119,104 @@ async fn fetch_one<'a>(client: &Client, rl: &RateLimiter, doi: &'a str) -> Optio
     }
 }
 
-pub fn fetch_and_validate<'a>(
-    options: &ClArgs,
-    dois: impl IntoIterator<Item = &'a str>,
-    dump_raw: Option<impl AsRef<Path>>,
-) -> Result<Vec<(&'a str, JsonValue)>> {
+fn build_runtime(jobs: usize) -> Result<tokio::runtime::Runtime> {
+    let mut builder = if jobs > 1 {
+        let mut b = tokio::runtime::Builder::new_multi_thread();
+        b.worker_threads(jobs);
+        b
+    } else {
+        tokio::runtime::Builder::new_current_thread()
+    };
+    let runtime = builder.enable_time().enable_io().build()?;
+    Ok(runtime)
+}
+
+/// The successfully fetched and validated entries, and the DOIs which failed
+type FetchResults<'a> = (Vec<(&'a str, JsonValue)>, Vec<&'a str>);
+
+/// How often progress is logged while fetching
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A quota of `rate` requests per second.  Up to `rate` requests may be made at once, as long as
+/// they are spread out on average, but at least one.
+fn quota(rate: f64) -> Result<gv::Quota> {
+    if !(rate.is_finite() && rate > 0.0) {
+        bail!("request rate must be a positive number, not {}", rate);
+    }
+    let burst = NonZeroU32::new((rate as u32).max(1)).unwrap();
+    let period = match Duration::try_from_secs_f64(1.0 / rate) {
+        Ok(period) => period,
+        Err(_) => bail!("request rate {} is too low", rate),
+    };
+    let quota = gv::Quota::with_period(period)
+        .ok_or_else(|| anyhow!("request rate {} is too high", rate))?;
+    Ok(quota.allow_burst(burst))
+}
+
+fn build_client(timeout: Duration) -> Result<Client> {
     let mut headers = header::HeaderMap::new();
     headers.insert(
         header::ACCEPT,
         "application/vnd.citationstyles.csl+json".parse().unwrap(),
     );
     debug!(?headers);
-    let client = Client::builder().default_headers(headers).build()?;
+    let client = Client::builder()
+        .default_headers(headers)
+        .timeout(timeout)
+        .build()?;
+    Ok(client)
+}
+
+pub fn fetch_and_validate<'a>(
+    options: &ClArgs,
+    dois: impl IntoIterator<Item = &'a str>,
+    dump_raw: Option<impl AsRef<Path>>,
+) -> Result<FetchResults<'a>> {
+    let client = build_client(Duration::from_secs(options.timeout))?;
 
-    let rl = RateLimiter::direct(gv::Quota::per_second(
-        NonZeroU32::new(options.max_requests_per_sec).unwrap(),
-    ));
+    // The rate limiter is shared by all tasks, so requests are paced globally regardless of how
+    // many worker threads are running them.
+    let rl = Arc::new(RateLimiter::direct(quota(options.max_requests_per_sec)?));
 
-    let runtime = tokio::runtime::Builder::new_current_thread()
-        .worker_threads(1)
-        .enable_time()
-        .enable_io()
-        .build()?;
+    let runtime = build_runtime(options.jobs)?;
+    let resolver: Arc<str> = options.resolver_url.as_str().into();
 
-    let tasks = dois.into_iter().map(|doi| {
-        let client = &client;
-        let rl = &rl;
-        async move { (doi, fetch_one(&client, &rl, doi).await) }
-    });
+    let dois: Vec<_> = dois.into_iter().collect();
+    let count_total = dois.len();
+    let count_done = std::cell::Cell::new(0);
+    let count_done = &count_done;
+    let last_progress = std::cell::Cell::new(std::time::Instant::now());
+    let last_progress = &last_progress;
+
+    let tasks: Vec<_> = dois
+        .into_iter()
+        .map(|doi| {
+            let client = client.clone();
+            let rl = rl.clone();
+            let resolver = resolver.clone();
+            let owned_doi = doi.to_string();
+            let handle =
+                runtime.spawn(async move { fetch_one(&client, &rl, &resolver, &owned_doi).await });
+            async move {
+                let json = handle.await.unwrap_or_else(|err| {
+                    error!(doi, %err, "fetch task failed");
+                    None
+                });
+                count_done.set(count_done.get() + 1);
+                if last_progress.get().elapsed() >= PROGRESS_INTERVAL {
+                    last_progress.set(std::time::Instant::now());
+                    info!(done = count_done.get(), count_total, "fetching");
+                }
+                (doi, json)
+            }
+        })
+        .collect();
 
     let fetch_results = runtime.block_on(future::join_all(tasks));
     drop(runtime);
 
     let mut results = Vec::with_capacity(fetch_results.len());
-    let count_total = fetch_results.len();
+    let mut failed = Vec::new();
 
     let mut raw = if dump_raw.is_some() {
         Some(Vec::with_capacity(fetch_results.len()))
@@ -246,7 +227,10 @@ pub fn fetch_and_validate<'a>(
     for (doi, json) in fetch_results {
         let mut json = match json {
             Some(v) => v,
-            None => continue,
+            None => {
+                failed.push(doi);
+                continue;
+            }
         };
 
         if let Some(raw) = &mut raw {
@@ -254,12 +238,21 @@ pub fn fetch_and_validate<'a>(
         }
 
         let _s = error_span!("validate", doi).entered();
-        clean_json(&mut json);
+        clean_json(&mut json, &options.clean);
 
-        if !validate::validate_entry(&json, validate::ignore_missing_id) {
+        let ignore =
+            |e: &ValidationError| validate::ignore_missing_id(e) || options.clean.ignore_kept(e);
+        if !validate::validate_entry(&json, ignore) {
+            failed.push(doi);
             continue;
         }
 
+        if let Some(title) = json.get("title").and_then(JsonValue::as_str) {
+            if let Some(reason) = suspicious_title(title) {
+                warn!(doi, title, "{}, the metadata may be incomplete", reason);
+            }
+        }
+
         results.push((doi, json))
     }
 
@@ -272,71 +265,241 @@ pub fn fetch_and_validate<'a>(
         count_successful = results.len(),
         "fetch complete"
     );
-    Ok(results)
+    Ok((results, failed))
 }
 
-fn merge_one(entry: &mut CslEntry, new: &CslEntry) {
+/// Returns the names of the fields which were added to `entry`
+fn merge_one<'a>(entry: &mut CslEntry, new: &'a CslEntry) -> Vec<&'a str> {
+    let mut added = Vec::new();
     for (field, val) in new {
         if !entry.contains_key(field) {
             entry.insert(field.clone(), val.clone());
+            added.push(field.as_str());
         }
     }
+    added
+}
+
+/// Returned by `--check` when some DOIs could not be fetched or validated
+#[derive(Debug, Clone, Copy)]
+pub struct FetchFailed(pub usize);
+
+impl std::fmt::Display for FetchFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} DOI(s) could not be fetched or validated", self.0)
+    }
+}
+
+impl std::error::Error for FetchFailed {}
+
+lazy_static! {
+    pub static ref DOI_IN_TEXT: regex::Regex =
+        regex::Regex::new(r#"10\.\d{4,9}/[^\s"<>]+"#).unwrap();
 }
 
-pub fn fetch_and_merge(options: &ClArgs, db: &mut Vec<CslEntry>) -> Result<()> {
-    let mut cache = cache::FetchCache::load()?;
+/// The DOI of `entry`, taken from `field`.  The default `DOI` field is matched
+/// case-insensitively.  If the field holds more than a DOI, like a URL or a note, the first DOI
+/// in it is used.
+fn entry_doi<'a>(entry: &'a CslEntry, field: &str) -> Option<&'a str> {
+    let value = if field == csl::DOI {
+        entry.iter().find(|(k, _)| k.eq_ignore_ascii_case(field))?.1
+    } else {
+        entry.get(field)?
+    };
+    let doi = DOI_IN_TEXT.find(value.as_str()?)?.as_str();
+    Some(doi.trim_end_matches(['.', ',', ';']))
+}
+
+/// The ids of the entries which have no DOI, and so can't be fetched
+fn missing_doi(db: &[CslEntry], doi_field: &str) -> Vec<String> {
+    db.iter()
+        .filter(|e| entry_doi(e, doi_field).is_none())
+        .map(|e| match e.get(csl::ID) {
+            Some(JsonValue::String(id)) => id.clone(),
+            Some(id) => id.to_string(),
+            None => "?".to_string(),
+        })
+        .collect()
+}
 
-    let to_fetch: Vec<_> = db
+/// Returns the number of DOIs which failed to fetch or validate
+pub fn fetch_and_merge(options: &ClArgs, db: &mut [CslEntry]) -> Result<usize> {
+    let missing = missing_doi(db, &options.doi_from);
+    if !missing.is_empty() {
+        let ids = missing.join(", ");
+        if options.require_doi {
+            bail!(
+                "{} entries have no DOI and --require-doi was given: {}",
+                missing.len(),
+                ids
+            );
+        }
+        warn!(count = missing.len(), %ids, "entries without a DOI will not be fetched");
+    }
+
+    // Starting from an empty cache fetches every DOI, including those which failed recently
+    let fresh = options.no_cache || options.refresh_cache;
+    let mut cache = if fresh {
+        cache::FetchCache::default()
+    } else {
+        cache::FetchCache::load()?
+    };
+
+    let mut dois: Vec<_> = db
         .iter()
-        .filter_map(|e| e.get("DOI").map(JsonExt::unwrap_str))
+        .filter_map(|e| entry_doi(e, &options.doi_from))
+        .map(cache::normalize_doi)
         .filter(|doi| !cache.contains(doi))
         .collect();
+    dois.sort();
+    dois.dedup();
+    if options.offline && !dois.is_empty() {
+        for doi in &dois {
+            error!(doi = doi.as_str(), "not in cache");
+        }
+        bail!(
+            "{} DOI(s) are not in the cache and --offline was given",
+            dois.len()
+        );
+    }
+
+    let (to_fetch, skipped): (Vec<_>, Vec<_>) = dois
+        .iter()
+        .map(String::as_str)
+        .partition(|doi| options.retry_failed || !cache.failed_recently(doi));
     let count = to_fetch.len();
+    let mut count_failed = skipped.len();
+
+    if !skipped.is_empty() {
+        info!(
+            count = skipped.len(),
+            "skipping DOIs which failed recently, use --retry-failed to fetch them anyway"
+        );
+    }
+
+    if options.dry_run {
+        info!(count, "dry run, the following DOIs would be fetched");
+        for doi in to_fetch {
+            println!("{}", doi);
+        }
+        return Ok(0);
+    }
 
     if count > 0 {
         info!(count, "retrieving entries");
-        for (doi, json) in fetch_and_validate(options, to_fetch, options.dump_raw())? {
-            cache.insert(doi.to_string(), json.unwrap_object());
+        let (results, failed) = fetch_and_validate(options, to_fetch, options.dump_raw())?;
+        for (doi, json) in results {
+            cache.insert(doi, json.unwrap_object());
         }
-        cache.save()?;
-    } else {
+        count_failed += failed.len();
+        for doi in failed {
+            cache.insert_failed(doi);
+        }
+        if options.refresh_cache {
+            let mut stored = cache::FetchCache::load()?;
+            let count = stored.merge(cache.clone());
+            stored.save()?;
+            info!(count, "refreshed cached entries");
+        } else if !fresh {
+            cache.save()?;
+        }
+    } else if skipped.is_empty() {
         info!("all entries found in cache")
     }
 
+    merge_cached(&cache, db, &options.doi_from);
+    Ok(count_failed)
+}
+
+/// Add the fields of each entry's cached Crossref data which the entry doesn't already have.
+/// Entries whose DOI isn't cached are left alone.
+pub fn merge_cached(cache: &cache::FetchCache, db: &mut [CslEntry], doi_from: &str) {
+    let mut changes = Vec::new();
     for e in db {
-        if let Some(doi) = e.get("DOI") {
-            if let Some(update) = cache.get(doi.unwrap_str()) {
-                merge_one(e, update);
+        if let Some(doi) = entry_doi(e, doi_from) {
+            let doi = doi.to_string();
+            if let Some(update) = cache.get(&doi) {
+                let added = merge_one(e, update);
+                if !added.is_empty() {
+                    let id = e.get("id").and_then(JsonValue::as_str).unwrap_or(&doi);
+                    changes.push(format!("added {} to {}", added.join(","), id));
+                }
             }
         }
     }
 
-    Ok(())
+    if changes.is_empty() {
+        info!("no fields added to any entry");
+    } else {
+        info!("{}", changes.join("; "));
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
 pub enum OutputFormat {
     Json,
+    Yaml,
     Biblatex,
+    /// A summary of each entry's key, type, title and year
+    Toml,
+    /// The citation key of each entry which converts to BibLaTeX, one per line
+    Keys,
 }
 
 impl OutputFormat {
     pub fn suffix(&self) -> &'static str {
         match self {
             OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
             OutputFormat::Biblatex => "bib",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Keys => "txt",
         }
     }
 }
 
 #[derive(Args)]
 pub struct ClArgs {
-    /// Input file (CSL JSON format)
-    input: PathBuf,
-
-    /// Maximum number of API requests allowed per second.
-    #[clap(short = 'r', default_value_t = 20)]
-    max_requests_per_sec: u32,
+    /// Input files (CSL JSON format, or YAML if the extension is .yaml or .yml), or glob patterns
+    /// such as "refs/*.json".  The entries of several files are merged into one database, and ids
+    /// must be unique across all of them.  Use "-" for STDIN, which may be JSON or YAML.
+    #[clap(required = true)]
+    input: Vec<PathBuf>,
+
+    /// Merge the fetched entries into this CSL database (JSON, or YAML if the extension is .yaml
+    /// or .yml) and write it back, instead of writing any other output.  An entry with the same
+    /// DOI or id as an existing one only adds the fields it lacks, so curated values are kept.
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = &["output", "update", "append", "validate-only", "dry-run"]
+    )]
+    merge_into: Option<PathBuf>,
+
+    /// Read the inputs as lists of DOIs, one per line, instead of CSL.  Implied for inputs with
+    /// a .txt extension.  Each DOI is fetched and given a key like `smith2020title`.
+    #[clap(long)]
+    dois: bool,
+
+    /// Maximum number of API requests allowed per second.  Rates below 1 space requests further
+    /// apart, e.g. 0.2 for one request every 5 seconds.
+    #[clap(short = 'r', default_value_t = 20.0)]
+    max_requests_per_sec: f64,
+
+    /// Timeout for each request in seconds.  Requests which time out are treated like any other
+    /// failed fetch.
+    #[clap(long, value_name = "SECS", default_value_t = 30)]
+    timeout: u64,
+
+    /// Base URL of the DOI resolver.  Requests go through the proxy given by the HTTPS_PROXY
+    /// environment variable, if it is set.
+    #[clap(long, value_name = "URL", default_value = "https://doi.org")]
+    resolver_url: String,
+
+    /// Number of worker threads used to fetch and parse responses.  With 1 (the default), a
+    /// single-threaded runtime is used.
+    #[clap(short = 'j', long, default_value_t = 1)]
+    jobs: usize,
 
     /// Dump the raw JSON retrieved, prior to cleaning.
     #[cfg(debug_assertions)]
@@ -347,18 +510,103 @@ pub struct ClArgs {
     #[clap(arg_enum, short='f', default_value_t=OutputFormat::Biblatex)]
     format: OutputFormat,
 
-    /// Output path. Default is same name as input file with "-filled" appended to the filename stem. Use "-"
-    /// for STDOUT.
+    /// Output path. Default is same name as input file with --suffix appended to the filename stem, or
+    /// STDOUT if reading from STDIN. Use "-" for STDOUT.
     #[clap(short = 'o')]
     output: Option<String>,
 
+    /// Highlight BibLaTeX written to STDOUT.  Output files are never highlighted.
+    #[clap(arg_enum, long, value_name = "WHEN", default_value_t = color::ColorChoice::Auto)]
+    color: color::ColorChoice,
+
+    /// Appended to the input file stem to make the default output path.  Use "" to name the
+    /// output after the input, e.g. refs.json -> refs.bib.
+    #[clap(long, default_value = "-filled", allow_hyphen_values = true)]
+    suffix: String,
+
     /// Output a single entry only, useful for debugging.
     #[clap(short = 'e')]
     entry: Option<String>,
 
+    /// Only output entries issued in or after this year
+    #[clap(long, value_name = "YEAR")]
+    since: Option<i32>,
+
+    /// Only output entries issued in or before this year
+    #[clap(long, value_name = "YEAR")]
+    until: Option<i32>,
+
+    /// Drop entries without a parseable issued year when filtering with --since or --until,
+    /// instead of keeping them.
+    #[clap(long)]
+    strict_years: bool,
+
+    /// Only write these fields of each entry, in this order (JSON and YAML output only).
+    #[clap(long, value_name = "FIELD", use_value_delimiter = true)]
+    fields: Vec<String>,
+
     /// Ignore and skip over entries with errors
     #[clap(short = 'c')]
     ignore_errors: bool,
+
+    /// Print the DOIs which are not in the cache and would be fetched, then exit without making
+    /// any requests or writing output.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Load, validate, fetch and convert the entries as usual, then print a summary instead of
+    /// writing any output.
+    #[clap(long, conflicts_with_all = &["dry-run", "update", "append"])]
+    validate_only: bool,
+
+    /// Never make network requests: fail, listing the DOIs which are not in the cache, unless
+    /// every DOI is already cached.
+    #[clap(long, conflicts_with = "retry-failed")]
+    offline: bool,
+
+    /// Ignore the cache and fetch every DOI, without saving the results.  Use this to check a
+    /// database against live Crossref data when the cache may be stale.
+    #[clap(long, conflicts_with = "offline")]
+    no_cache: bool,
+
+    /// Like --no-cache, but replace the cached entries with the fresh results.  DOIs which fail
+    /// to fetch keep their cached entry.
+    #[clap(long, conflicts_with_all = &["offline", "no-cache"])]
+    refresh_cache: bool,
+
+    /// Fetch DOIs again even if they failed within the last week.  Failures are otherwise cached
+    /// and skipped.
+    #[clap(long)]
+    retry_failed: bool,
+
+    /// Only convert entries which changed since the last run, copying the rest from a manifest
+    /// saved next to the output file (e.g. refs.bib.manifest.json).  BibLaTeX output only.
+    #[clap(long, conflicts_with = "crossref-proceedings")]
+    update: bool,
+
+    /// Append to the output file instead of overwriting it, skipping entries whose key is already
+    /// in the file.  BibLaTeX output only.
+    #[clap(long, conflicts_with = "update")]
+    append: bool,
+
+    /// The field holding each entry's DOI.  The default also matches `doi`.  If the field holds
+    /// more than a DOI, such as a URL or a note, the first DOI in it is used.
+    #[clap(long, value_name = "FIELD", default_value = "DOI")]
+    doi_from: String,
+
+    /// Exit with an error if any entry has no DOI, instead of warning.
+    #[clap(long)]
+    require_doi: bool,
+
+    /// Exit with an error if any DOI could not be fetched or validated.
+    #[clap(long, alias = "strict")]
+    check: bool,
+
+    #[clap(flatten)]
+    clean: CleanOptions,
+
+    #[clap(flatten)]
+    convert: convert::ConvertOptions,
 }
 
 impl ClArgs {
@@ -374,90 +622,531 @@ impl ClArgs {
     }
 }
 
-fn output_json(db: Vec<CslEntry>, path: Option<impl AsRef<Path>>) -> Result<()> {
+/// Keep only `fields` of `entry`, in the given order
+fn project(mut entry: CslEntry, fields: &[String]) -> CslEntry {
+    fields
+        .iter()
+        .filter_map(|f| Some((f.clone(), entry.remove(f)?)))
+        .collect()
+}
+
+/// Sort the fields of `entry` and of any objects inside it, so the output doesn't depend on the
+/// order fields were added in.  `id` and `type` come first, then the rest by name.
+pub fn canonical_order(entry: CslEntry) -> CslEntry {
+    fn rank(field: &str) -> u8 {
+        match field {
+            csl::ID => 0,
+            csl::TYPE => 1,
+            _ => 2,
+        }
+    }
+
+    fn sort_nested(v: &mut JsonValue) {
+        match v {
+            JsonValue::Object(map) => *map = canonical_order(std::mem::take(map)),
+            JsonValue::Array(values) => values.iter_mut().for_each(sort_nested),
+            _ => {}
+        }
+    }
+
+    let mut fields: Vec<_> = entry.into_iter().collect();
+    fields.iter_mut().for_each(|(_, v)| sort_nested(v));
+    fields.sort_by(|(a, _), (b, _)| rank(a).cmp(&rank(b)).then_with(|| a.cmp(b)));
+    fields.into_iter().collect()
+}
+
+fn output_json(
+    db: Vec<CslEntry>,
+    path: Option<impl AsRef<Path>>,
+    fields: &[String],
+    yaml: bool,
+) -> Result<()> {
+    let db = db.into_iter().map(canonical_order);
+    let db: Vec<_> = if fields.is_empty() {
+        db.collect()
+    } else {
+        db.map(|e| project(e, fields)).collect()
+    };
+    match (path, yaml) {
+        (Some(path), false) => write_json_pretty(path, &db),
+        (Some(path), true) => write_yaml(path, &db),
+        (None, false) => {
+            let out = std::io::stdout();
+            serde_json::to_writer_pretty(out.lock(), &db)?;
+            Ok(())
+        }
+        (None, true) => {
+            let out = std::io::stdout();
+            serde_yaml::to_writer(out.lock(), &db)?;
+            Ok(())
+        }
+    }
+}
+
+/// Merge `db` into the CSL database at `path`, for `--merge-into`.  The file is created if it
+/// doesn't exist.
+fn merge_into(db: Vec<CslEntry>, path: &Path, options: &ClArgs) -> Result<()> {
+    let mut existing: Vec<CslEntry> = if path.exists() {
+        validate::load_and_validate_db(&[path], options.ignore_errors)?
+            .into_iter()
+            .map(JsonExt::unwrap_object)
+            .collect()
+    } else {
+        info!(path=%path.display(), "creating new database");
+        Vec::new()
+    };
+    let (count_added, count_updated) = merge_entries(&mut existing, db, &options.doi_from);
+    info!(count_added, count_updated, path=%path.display(), "merged into database");
+
+    if is_yaml(path) {
+        write_yaml(path, &existing)
+    } else {
+        write_json_pretty(path, &existing)
+    }
+}
+
+/// Merge `db` into `existing`.  An entry is matched to an existing one by DOI, or else by id, and
+/// only adds the fields the existing entry lacks.  Unmatched entries are appended, except those
+/// whose id is taken by an entry with a different DOI.  Returns the number of entries added and
+/// updated.
+fn merge_entries(
+    existing: &mut Vec<CslEntry>,
+    db: Vec<CslEntry>,
+    doi_from: &str,
+) -> (usize, usize) {
+    let doi = |e: &CslEntry| entry_doi(e, doi_from).map(cache::normalize_doi);
+    let id = |e: &CslEntry| e.get(csl::ID).and_then(JsonValue::as_str).map(String::from);
+
+    let mut by_doi = HashMap::new();
+    let mut by_id = HashMap::new();
+    for (i, e) in existing.iter().enumerate() {
+        by_doi.extend(doi(e).map(|d| (d, i)));
+        by_id.extend(id(e).map(|id| (id, i)));
+    }
+
+    let mut count_added = 0;
+    let mut count_updated = 0;
+    for e in db {
+        let new_doi = doi(&e);
+        let new_id = id(&e);
+        let found = match new_doi.as_ref().and_then(|d| by_doi.get(d)) {
+            Some(&i) => Some(i),
+            None => match new_id.as_ref().and_then(|id| by_id.get(id)) {
+                Some(&i) if new_doi.is_some() && doi(&existing[i]).is_some() => {
+                    warn!(id = ?new_id, "id is taken by an entry with a different DOI, skipping");
+                    continue;
+                }
+                found => found.copied(),
+            },
+        };
+        match found {
+            Some(i) => {
+                let added = merge_one(&mut existing[i], &e);
+                if !added.is_empty() {
+                    debug!(id = ?id(&existing[i]), added = %added.join(","), "updated entry");
+                    count_updated += 1;
+                }
+            }
+            None => {
+                let i = existing.len();
+                by_doi.extend(new_doi.map(|d| (d, i)));
+                by_id.extend(new_id.map(|id| (id, i)));
+                existing.push(canonical_order(e));
+                count_added += 1;
+            }
+        }
+    }
+    (count_added, count_updated)
+}
+
+/// Keep the entries issued between `since` and `until` (inclusive), for `--since` and `--until`.
+/// Entries without a parseable `issued` year are kept, unless `strict` is true.
+fn filter_years(
+    db: &mut Vec<CslEntry>,
+    since: Option<i32>,
+    until: Option<i32>,
+    strict: bool,
+) -> Result<()> {
+    if let (Some(since), Some(until)) = (since, until) {
+        if since > until {
+            bail!("--since {} is after --until {}", since, until);
+        }
+    }
+    if since.is_none() && until.is_none() {
+        return Ok(());
+    }
+    let count = db.len();
+    let mut count_undated = 0;
+    db.retain(|e| match convert::issued_year(e) {
+        Ok(Some(year)) => {
+            !matches!(since, Some(since) if year < since)
+                && !matches!(until, Some(until) if year > until)
+        }
+        _ => {
+            debug!(id = ?e.get(csl::ID), "no issued year");
+            count_undated += 1;
+            !strict
+        }
+    });
+    if count_undated > 0 && strict {
+        info!(count_undated, "dropped entries without an issued year");
+    } else if count_undated > 0 {
+        warn!(
+            count_undated,
+            "kept entries without an issued year, use --strict-years to drop them"
+        );
+    }
+    info!(
+        count_dropped = count - db.len(),
+        count_kept = db.len(),
+        "filtered entries by year"
+    );
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    entry: Vec<SummaryEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct SummaryEntry<'a> {
+    key: &'a str,
+    #[serde(rename = "type")]
+    ty: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    year: Option<i32>,
+}
+
+impl<'a> SummaryEntry<'a> {
+    fn new(e: &'a CslEntry) -> Self {
+        let field = |f| e.get(f).and_then(JsonValue::as_str);
+        let year = convert::issued_year(e).unwrap_or_else(|err| {
+            warn!(id = field(csl::ID), "{:#}", err);
+            None
+        });
+        SummaryEntry {
+            key: field(csl::ID).unwrap_or_default(),
+            ty: field(csl::TYPE).unwrap_or_default(),
+            title: field(csl::TITLE),
+            year,
+        }
+    }
+}
+
+fn output_toml(db: &[CslEntry], path: Option<impl AsRef<Path>>) -> Result<()> {
+    let summary = Summary {
+        entry: db.iter().map(SummaryEntry::new).collect(),
+    };
+    let s = toml::to_string(&summary)?;
     if let Some(path) = path {
-        write_json_pretty(path, &db)
+        let path = path.as_ref();
+        std::fs::write(path, s).context_write(path)?;
     } else {
-        let out = std::io::stdout();
-        serde_json::to_writer_pretty(out.lock(), &db)?;
-        Ok(())
+        print!("{}", s);
+    }
+    Ok(())
+}
+
+/// Convert the entries of `db` to BibLaTeX, as they will be written
+pub fn convert_db(
+    db: Vec<CslEntry>,
+    ignore_errors: bool,
+    options: &convert::ConvertOptions,
+) -> Result<biblatex::Bibliography> {
+    let mut entries = Vec::with_capacity(db.len());
+    for e in db {
+        match convert::csl_to_biblatex(e, options) {
+            Ok(e) => entries.push(e),
+            Err(e) if !ignore_errors => return Err(e),
+            Err(_) => {}
+        }
+    }
+    convert::crossref_proceedings(&mut entries, options);
+    let mut bib = biblatex::Bibliography(entries);
+    for id in bib.dedup() {
+        warn!(id = %id, "skipping entry with duplicate key");
+    }
+    Ok(bib)
+}
+
+fn output_keys(
+    db: Vec<CslEntry>,
+    path: Option<impl AsRef<Path>>,
+    ignore_errors: bool,
+    options: &convert::ConvertOptions,
+) -> Result<()> {
+    let bib = convert_db(db, ignore_errors, options)?;
+    let mut keys = String::new();
+    for e in &bib.0 {
+        keys += e.id();
+        keys.push('\n');
     }
+    if let Some(path) = path {
+        let path = path.as_ref();
+        std::fs::write(path, keys).context_write(path)?;
+    } else {
+        print!("{}", keys);
+    }
+    Ok(())
 }
 
 fn output_biblatex(
     db: Vec<CslEntry>,
     path: Option<impl AsRef<Path>>,
+    append: bool,
     ignore_errors: bool,
+    color: color::ColorChoice,
+    options: &convert::ConvertOptions,
 ) -> Result<()> {
+    use std::fmt::Write as _;
     use std::io::Write;
 
-    fn write<W: Write, I: IntoIterator<Item = Result<biblatex::entry::Entry>>>(
-        db: I,
-        mut w: W,
-        ignore_errors: bool,
-    ) -> Result<()> {
-        for e in db {
-            match e {
-                Ok(e) => write!(w, "{}\n", e.biblatex())?,
-                Err(e) => {
-                    if !ignore_errors {
-                        return Err(e);
-                    }
-                }
+    let bib = convert_db(db, ignore_errors, options)?;
+
+    match path {
+        Some(path) if append => append_biblatex(bib, path.as_ref(), options)?,
+        Some(path) => {
+            let path = path.as_ref();
+            let mut file = std::fs::File::create(path)
+                .context_write(path)
+                .map(std::io::BufWriter::new)?;
+            let bib = bib
+                .with_field_names(options.field_names())
+                .on_unknown(options.unknown_char_policy());
+            write!(file, "{}", bib)?;
+        }
+        None => {
+            let mut text = String::new();
+            let bib = bib
+                .with_field_names(options.field_names())
+                .on_unknown(options.unknown_char_policy());
+            write!(text, "{}", bib)
+                .map_err(|_| anyhow!("failed to write the output as BibLaTeX"))?;
+            if color.enabled() {
+                text = color::highlight_biblatex(&text);
             }
+            let out = std::io::stdout();
+            write!(out.lock(), "{}", text)?;
         }
-        Ok(())
     }
+    Ok(())
+}
 
-    let db = db.into_iter().map(convert::csl_to_biblatex);
+/// Append the entries of `bib` whose keys are not already in the file at `path`
+fn append_biblatex(
+    mut bib: biblatex::Bibliography,
+    path: &Path,
+    options: &convert::ConvertOptions,
+) -> Result<()> {
+    use std::io::Write;
 
-    if let Some(path) = path {
-        let path = path.as_ref();
-        let file = std::fs::File::create(path)
-            .context_write(path)
-            .map(std::io::BufWriter::new)?;
-        write(db, file, ignore_errors)?;
-    } else {
-        let out = std::io::stdout();
-        write(db, out.lock(), ignore_errors)?;
+    let existing = match std::fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).context_read(path),
+    };
+    let keys: HashSet<_> = biblatex::entry_keys(&existing).into_iter().collect();
+    let count = bib.0.len();
+    bib.0.retain(|e| {
+        let new = !keys.contains(e.id());
+        if !new {
+            debug!(id = e.id(), "entry is already in the output file");
+        }
+        new
+    });
+    info!(
+        count_appended = bib.0.len(),
+        count_skipped = count - bib.0.len(),
+        "appending to output"
+    );
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context_write(path)
+        .map(std::io::BufWriter::new)?;
+    // Keep a blank line between the existing entries and the new ones
+    if !existing.is_empty() && !bib.0.is_empty() {
+        let newlines = existing.len() - existing.trim_end_matches('\n').len();
+        for _ in newlines..2 {
+            writeln!(file)?;
+        }
     }
+    let bib = bib
+        .with_field_names(options.field_names())
+        .on_unknown(options.unknown_char_policy());
+    write!(file, "{}", bib)?;
     Ok(())
 }
 
-pub fn main(mut args: ClArgs) -> Result<()> {
-    args.max_requests_per_sec = args.max_requests_per_sec.max(1);
-    let mut db: Vec<_> = validate::load_and_validate_db(&args.input, args.ignore_errors)?
-        .into_iter()
-        .map(JsonExt::unwrap_object)
+/// Summarise how much of `db` was found in the cache and converts to BibLaTeX, for
+/// `--validate-only`.  Fails like a normal run would, but writes nothing.
+fn validate_only(args: &ClArgs, db: Vec<CslEntry>, count_failed: usize) -> Result<()> {
+    let cache = cache::FetchCache::load()?;
+    let dois: Vec<_> = db
+        .iter()
+        .filter_map(|e| entry_doi(e, &args.doi_from))
         .collect();
-    info!(n_entries = db.len(), "DB read successfully");
+    let count_dois = dois.len();
+    let count_cached = dois.iter().filter(|doi| cache.contains(doi)).count();
+    let count_entries = db.len();
+    let mut count_converted = 0;
+    for e in db {
+        if convert::csl_to_biblatex(e, &args.convert).is_ok() {
+            count_converted += 1;
+        }
+    }
 
-    fetch_and_merge(&args, &mut db)?;
+    let percent = |n: usize, total: usize| match total {
+        0 => 100.0,
+        t => 100.0 * n as f64 / t as f64,
+    };
+    println!("{:<16}{}", "Entries:", count_entries);
+    println!("{:<16}{}", "With a DOI:", count_dois);
+    println!(
+        "{:<16}{} ({:.0}% of DOIs)",
+        "Enriched:",
+        count_cached,
+        percent(count_cached, count_dois)
+    );
+    println!("{:<16}{}", "Failed DOIs:", count_failed);
+    println!(
+        "{:<16}{} ({:.0}% of entries)",
+        "Converted:",
+        count_converted,
+        percent(count_converted, count_entries)
+    );
 
-    let output_file = match args.output.take() {
-        None => {
-            let mut n = args.input.file_stem().expect("no file name").to_os_string();
-            n.push("-filled.");
+    if count_converted < count_entries && !args.ignore_errors {
+        bail!(
+            "{} entries could not be converted",
+            count_entries - count_converted
+        );
+    }
+    if args.check && count_failed > 0 {
+        error!(count_failed, "some DOIs could not be fetched or validated");
+        return Err(FetchFailed(count_failed).into());
+    }
+    Ok(())
+}
+
+pub fn main(mut args: ClArgs) -> Result<()> {
+    // Check the rate before reading anything
+    quota(args.max_requests_per_sec)?;
+    args.convert.load_tables()?;
+    if !args.fields.is_empty() && !matches!(args.format, OutputFormat::Json | OutputFormat::Yaml) {
+        bail!("--fields can only be used with JSON or YAML output (-f json, -f yaml)");
+    }
+    if args.update && args.format != OutputFormat::Biblatex {
+        bail!("--update can only be used with BibLaTeX output (-f biblatex)");
+    }
+    if args.append && args.format != OutputFormat::Biblatex {
+        bail!("--append can only be used with BibLaTeX output (-f biblatex)");
+    }
+    let inputs = validate::expand_inputs(&args.input)?;
+    let output_file = match (args.output.take(), inputs.as_slice()) {
+        _ if args.validate_only || args.merge_into.is_some() => None,
+        (None, [input]) if is_stdio(input) => None,
+        (None, [input]) => {
+            let mut n = input.file_stem().expect("no file name").to_os_string();
+            n.push(&args.suffix);
+            n.push(".");
             n.push(args.format.suffix());
-            Some(args.input.with_file_name(n))
+            let path = input.with_file_name(n);
+            if &path == input {
+                bail!(
+                    "output path would overwrite the input file {}, use -o or --suffix",
+                    path.display()
+                );
+            }
+            Some(path)
         }
-        Some(s) if s == "-" => None,
-        Some(p) => Some(PathBuf::from(p)),
+        (None, _) => bail!("-o is required with several input files"),
+        (Some(s), _) if s == "-" => None,
+        (Some(p), _) => Some(PathBuf::from(p)),
     };
 
+    let (doi_lists, inputs): (Vec<_>, Vec<_>) = inputs
+        .into_iter()
+        .partition(|p| args.dois || dois::is_doi_list(p));
+    let mut db: Vec<_> = if inputs.is_empty() {
+        Vec::new()
+    } else {
+        validate::load_and_validate_db(&inputs, args.ignore_errors)?
+            .into_iter()
+            .map(JsonExt::unwrap_object)
+            .collect()
+    };
+    let count_csl = db.len();
+    db.extend(dois::read_doi_lists(&doi_lists, &args.doi_from)?);
+    info!(n_entries = db.len(), "DB read successfully");
+
+    let count_failed = fetch_and_merge(&args, &mut db)?;
+    if args.dry_run {
+        return Ok(());
+    }
+    if count_csl < db.len() {
+        let taken = db[..count_csl]
+            .iter()
+            .filter_map(|e| e.get(csl::ID)?.as_str().map(String::from))
+            .collect();
+        dois::assign_keys(&mut db[count_csl..], taken);
+    }
+
     if let Some(id) = args.entry.as_ref() {
         db.retain(|e| e["id"].as_str() == Some(id))
     }
+    filter_years(&mut db, args.since, args.until, args.strict_years)?;
+
+    if args.validate_only {
+        return validate_only(&args, db, count_failed);
+    }
+    if let Some(path) = &args.merge_into {
+        merge_into(db, path, &args)?;
+        if args.check && count_failed > 0 {
+            error!(count_failed, "some DOIs could not be fetched or validated");
+            return Err(FetchFailed(count_failed).into());
+        }
+        return Ok(());
+    }
 
     match args.format {
-        OutputFormat::Json => output_json(db, output_file.as_ref())?,
-        OutputFormat::Biblatex => output_biblatex(db, output_file.as_ref(), args.ignore_errors)?,
+        OutputFormat::Json => output_json(db, output_file.as_ref(), &args.fields, false)?,
+        OutputFormat::Yaml => output_json(db, output_file.as_ref(), &args.fields, true)?,
+        OutputFormat::Biblatex if args.update => match &output_file {
+            Some(path) => update::output_biblatex(db, path, args.ignore_errors, &args.convert)?,
+            None => bail!("--update needs an output file, not STDOUT"),
+        },
+        OutputFormat::Biblatex if args.append && output_file.is_none() => {
+            bail!("--append needs an output file, not STDOUT")
+        }
+        OutputFormat::Biblatex => output_biblatex(
+            db,
+            output_file.as_ref(),
+            args.append,
+            args.ignore_errors,
+            args.color,
+            &args.convert,
+        )?,
+        OutputFormat::Toml => output_toml(&db, output_file.as_ref())?,
+        OutputFormat::Keys => {
+            output_keys(db, output_file.as_ref(), args.ignore_errors, &args.convert)?
+        }
     }
 
     if let Some(p) = &output_file {
         info!(path=%p.display(), "wrote output file");
     }
 
+    if args.check && count_failed > 0 {
+        error!(count_failed, "some DOIs could not be fetched or validated");
+        return Err(FetchFailed(count_failed).into());
+    }
+
     Ok(())
 }
 
@@ -465,13 +1154,295 @@ pub fn main(mut args: ClArgs) -> Result<()> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn filter_years() -> Result<()> {
+        use serde_json::json;
+        let db = [
+            json!({ "id": "a", "issued": { "date-parts": [[1999]] } }),
+            json!({ "id": "b", "issued": { "date-parts": [[2005, 3]] } }),
+            json!({ "id": "c", "issued": { "date-parts": [[2010]] } }),
+            json!({ "id": "undated" }),
+        ]
+        .map(JsonExt::unwrap_object);
+        let ids = |since, until, strict| -> Result<Vec<String>> {
+            let mut db = db.to_vec();
+            super::filter_years(&mut db, since, until, strict)?;
+            Ok(db
+                .iter()
+                .map(|e| e["id"].as_str().unwrap().to_string())
+                .collect())
+        };
+        assert_eq!(ids(None, None, true)?, ["a", "b", "c", "undated"]);
+        assert_eq!(ids(Some(2005), None, false)?, ["b", "c", "undated"]);
+        assert_eq!(ids(None, Some(2005), true)?, ["a", "b"]);
+        assert_eq!(ids(Some(1999), Some(2010), true)?, ["a", "b", "c"]);
+        assert_eq!(ids(Some(2005), Some(2005), false)?, ["b", "undated"]);
+        assert!(ids(Some(2010), Some(1999), false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rate_quota() -> Result<()> {
+        assert_eq!(
+            quota(20.0)?,
+            gv::Quota::per_second(NonZeroU32::new(20).unwrap())
+        );
+        let slow = quota(0.2)?;
+        assert_eq!(slow.replenish_interval(), Duration::from_secs(5));
+        assert_eq!(slow.burst_size().get(), 1);
+        assert!(quota(0.0).is_err());
+        assert!(quota(f64::NAN).is_err());
+        assert!(quota(1e-20).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn merge_entries() {
+        use serde_json::json;
+        let mut existing = [
+            json!({ "id": "curated", "DOI": "10.1000/A", "title": "Curated" }),
+            json!({ "id": "nodoi", "title": "No DOI" }),
+            json!({ "id": "other", "DOI": "10.1000/other" }),
+        ]
+        .map(JsonExt::unwrap_object)
+        .to_vec();
+        let db = [
+            json!({ "id": "new", "DOI": "10.1000/a", "title": "Fetched", "volume": "3" }),
+            json!({ "id": "nodoi", "DOI": "10.1000/b", "page": "1-2" }),
+            json!({ "id": "other", "DOI": "10.1000/c" }),
+            json!({ "id": "fresh", "DOI": "10.1000/d" }),
+        ]
+        .map(JsonExt::unwrap_object)
+        .to_vec();
+        assert_eq!(super::merge_entries(&mut existing, db, csl::DOI), (1, 2));
+        assert_eq!(
+            JsonValue::from(existing),
+            json!([
+                { "id": "curated", "DOI": "10.1000/A", "title": "Curated", "volume": "3" },
+                { "id": "nodoi", "title": "No DOI", "DOI": "10.1000/b", "page": "1-2" },
+                { "id": "other", "DOI": "10.1000/other" },
+                { "id": "fresh", "DOI": "10.1000/d" },
+            ])
+        );
+    }
+
+    #[test]
+    fn missing_doi() {
+        use serde_json::json;
+        let db = [
+            json!({ "id": "a", "DOI": "10.1000/a" }),
+            json!({ "id": "b" }),
+            json!({ "id": 3 }),
+        ]
+        .map(JsonExt::unwrap_object);
+        assert_eq!(super::missing_doi(&db, csl::DOI), ["b", "3"]);
+    }
+
+    #[test]
+    fn entry_doi() {
+        use serde_json::json;
+        let doi = |entry: JsonValue, field: &str| {
+            super::entry_doi(&entry.unwrap_object(), field).map(String::from)
+        };
+        assert_eq!(
+            doi(json!({ "DOI": "10.1000/abc" }), "DOI").as_deref(),
+            Some("10.1000/abc")
+        );
+        assert_eq!(
+            doi(json!({ "doi": "10.1000/abc" }), "DOI").as_deref(),
+            Some("10.1000/abc")
+        );
+        assert_eq!(doi(json!({ "doi": "10.1000/abc" }), "Doi"), None);
+        assert_eq!(
+            doi(
+                json!({ "note": "See https://doi.org/10.1287/opre.46.3.316." }),
+                "note"
+            )
+            .as_deref(),
+            Some("10.1287/opre.46.3.316")
+        );
+        assert_eq!(doi(json!({ "note": "No identifier" }), "note"), None);
+    }
+
+    #[test]
+    fn canonical_order() {
+        use serde_json::json;
+        let entry = json!({
+            "title": "A title",
+            "issued": { "date-parts": [[2001]], "circa": true },
+            "author": [{ "given": "Jane", "family": "Smith" }],
+            "type": "book",
+            "DOI": "10.1000/xyz",
+            "id": "x",
+        });
+        let sorted = serde_json::to_string(&super::canonical_order(entry.unwrap_object())).unwrap();
+        assert_eq!(
+            sorted,
+            r#"{"id":"x","type":"book","DOI":"10.1000/xyz","author":[{"family":"Smith","given":"Jane"}],"issued":{"circa":true,"date-parts":[[2001]]},"title":"A title"}"#
+        );
+    }
+
+    #[test]
+    fn project() {
+        use serde_json::json;
+        let entry = json!({ "title": "A title", "id": "x", "type": "book", "page": "1-10" });
+        let fields = ["id", "type", "title", "volume"].map(String::from);
+        let projected = super::project(entry.unwrap_object(), &fields);
+        assert_eq!(
+            projected.keys().collect::<Vec<_>>(),
+            ["id", "type", "title"]
+        );
+    }
+
+    #[test]
+    fn toml_summary() -> Result<()> {
+        use serde_json::json;
+        let db = [
+            json!({ "id": "a", "type": "book", "title": "A", "issued": { "date-parts": [[2001]] } }),
+            json!({ "id": "b", "type": "report" }),
+        ]
+        .map(JsonExt::unwrap_object);
+        let summary = Summary {
+            entry: db.iter().map(SummaryEntry::new).collect(),
+        };
+        assert_eq!(
+            toml::to_string(&summary)?,
+            "[[entry]]\nkey = \"a\"\ntype = \"book\"\ntitle = \"A\"\nyear = 2001\n\n\
+             [[entry]]\nkey = \"b\"\ntype = \"report\"\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn yaml_roundtrip() -> Result<()> {
+        let db = validate::load_and_validate_db(&["tests/db.json"], false)?;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("db.yaml");
+        write_yaml(&path, &db)?;
+        let yaml = validate::load_and_validate_db(&[&path], false)?;
+        assert_eq!(db, yaml);
+        Ok(())
+    }
+
+    #[test]
+    fn append_new_entries() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("refs.bib");
+        std::fs::write(&path, "@article{barnhart1998,\n    title = {Kept},\n}\n")?;
+        let db: Vec<CslEntry> = ["article", "book"]
+            .iter()
+            .map(|name| {
+                read_json(format!(
+                    "{}/tests/biblatex/{}.json",
+                    env!("CARGO_MANIFEST_DIR"),
+                    name
+                ))
+            })
+            .collect::<Result<_>>()?;
+        let options = convert::ConvertOptions::default();
+        output_biblatex(
+            db.clone(),
+            Some(&path),
+            true,
+            false,
+            color::ColorChoice::Never,
+            &options,
+        )?;
+        output_biblatex(
+            db,
+            Some(&path),
+            true,
+            false,
+            color::ColorChoice::Never,
+            &options,
+        )?;
+        let bib = std::fs::read_to_string(&path)?;
+
+        assert_eq!(
+            biblatex::entry_keys(&bib),
+            ["barnhart1998", "minkowski1910"]
+        );
+        assert!(bib.starts_with("@article{barnhart1998,\n    title = {Kept},\n}\n\n@book{"));
+        Ok(())
+    }
+
+    #[test]
+    fn keep_and_drop_fields() {
+        use serde_json::json;
+        let options = CleanOptions {
+            keep: vec!["abstract".into(), "funder".into()],
+            drop: vec!["page".into()],
+            strip_doi_url: true,
+            first_only: vec!["title".into()],
+        };
+        let mut entry = json!({
+            "type": "article-journal",
+            "title": ["A title", "A subtitle"],
+            "abstract": "An abstract",
+            "funder": [{ "name": "A funder" }],
+            "license": [],
+            "page": "1-10",
+            "DOI": "10.1000/abc",
+            "URL": "http://dx.doi.org/10.1000/abc",
+        });
+        clean_json(&mut entry, &options);
+        assert_eq!(
+            entry,
+            json!({
+                "type": "article-journal",
+                "title": "A title",
+                "abstract": "An abstract",
+                "funder": [{ "name": "A funder" }],
+                "DOI": "10.1000/abc",
+            })
+        );
+
+        let ignore = |e: &ValidationError| validate::ignore_missing_id(e) || options.ignore_kept(e);
+        assert!(validate::validate_entry(&entry, ignore));
+        assert!(!validate::validate_entry(
+            &entry,
+            validate::ignore_missing_id
+        ));
+    }
+
+    #[tokio::test]
+    async fn fetch_from_resolver() {
+        use serde_json::json;
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let entry = json!({ "type": "article-journal", "DOI": "10.1000/abc" });
+        Mock::given(matchers::method("GET"))
+            .and(matchers::path("/10.1000%2Fabc"))
+            .and(matchers::header(
+                "accept",
+                "application/vnd.citationstyles.csl+json",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&entry))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = build_client(Duration::from_secs(5)).unwrap();
+        let rl = RateLimiter::direct(gv::Quota::per_second(NonZeroU32::new(100).unwrap()));
+        let resolver = format!("{}/", server.uri());
+        assert_eq!(
+            fetch_one(&client, &rl, &resolver, "10.1000/abc").await,
+            Some(entry)
+        );
+        assert_eq!(
+            fetch_one(&client, &rl, &resolver, "10.1000/xyz").await,
+            None
+        );
+    }
+
     #[test]
     fn clean() -> Result<()> {
         logging_init_test();
         let raw: Vec<JsonValue> = read_json("tests/raw-fetch.json")?;
         let mut ok = true;
         for mut entry in raw {
-            clean_json(&mut entry);
+            clean_json(&mut entry, &CleanOptions::default());
             ok &= validate::validate_entry(&entry, validate::ignore_missing_id);
         }
         assert!(ok);
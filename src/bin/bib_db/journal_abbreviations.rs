@@ -0,0 +1,146 @@
+//! ISO 4 journal title abbreviations, used by `--abbreviate-journal`.
+use super::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[rustfmt::skip]
+pub const BUNDLED: &[(&str, &str)] = &[
+    // Operations research and optimization
+    ("Annals of Operations Research", "Ann. Oper. Res."),
+    ("Computational Optimization and Applications", "Comput. Optim. Appl."),
+    ("Computers & Operations Research", "Comput. Oper. Res."),
+    ("Discrete Applied Mathematics", "Discrete Appl. Math."),
+    ("European Journal of Operational Research", "Eur. J. Oper. Res."),
+    ("IIE Transactions", "IIE Trans."),
+    ("INFORMS Journal on Computing", "INFORMS J. Comput."),
+    ("Journal of Global Optimization", "J. Glob. Optim."),
+    ("Journal of Optimization Theory and Applications", "J. Optim. Theory Appl."),
+    ("Journal of Scheduling", "J. Sched."),
+    ("Journal of the Operational Research Society", "J. Oper. Res. Soc."),
+    ("Management Science", "Manage. Sci."),
+    ("Manufacturing & Service Operations Management", "Manuf. Serv. Oper. Manag."),
+    ("Mathematical Programming", "Math. Program."),
+    ("Mathematical Programming Computation", "Math. Program. Comput."),
+    ("Mathematics of Operations Research", "Math. Oper. Res."),
+    ("Naval Research Logistics", "Nav. Res. Logist."),
+    ("Networks", "Networks"),
+    ("Omega", "Omega"),
+    ("Operations Research", "Oper. Res."),
+    ("Operations Research Letters", "Oper. Res. Lett."),
+    ("Production and Operations Management", "Prod. Oper. Manag."),
+    ("SIAM Journal on Computing", "SIAM J. Comput."),
+    ("SIAM Journal on Optimization", "SIAM J. Optim."),
+    ("SIAM Review", "SIAM Rev."),
+    ("Transportation Research Part B: Methodological", "Transp. Res. Part B Methodol."),
+    ("Transportation Research Part C: Emerging Technologies", "Transp. Res. Part C Emerg. Technol."),
+    ("Transportation Research Part E: Logistics and Transportation Review", "Transp. Res. Part E Logist. Transp. Rev."),
+    ("Transportation Science", "Transp. Sci."),
+    // Computer science
+    ("Communications of the ACM", "Commun. ACM"),
+    ("IEEE Transactions on Automatic Control", "IEEE Trans. Autom. Control"),
+    ("IEEE Transactions on Information Theory", "IEEE Trans. Inf. Theory"),
+    ("Journal of the ACM", "J. ACM"),
+    ("Journal of Machine Learning Research", "J. Mach. Learn. Res."),
+    // Statistics and economics
+    ("Annals of Statistics", "Ann. Stat."),
+    ("American Economic Review", "Am. Econ. Rev."),
+    ("Econometrica", "Econometrica"),
+    ("Journal of the American Statistical Association", "J. Am. Stat. Assoc."),
+    // General science
+    ("Journal of Computational Physics", "J. Comput. Phys."),
+    ("Nature", "Nature"),
+    ("Physical Review Letters", "Phys. Rev. Lett."),
+    ("Proceedings of the National Academy of Sciences", "Proc. Natl. Acad. Sci."),
+    ("Science", "Science"),
+];
+
+lazy_static! {
+    static ref BUNDLED_TABLE: Table = {
+        let mut t = Table::default();
+        for (title, abbrev) in BUNDLED {
+            t.insert(title, abbrev.to_string());
+        }
+        t
+    };
+}
+
+/// Normalise a title for lookup, so that case, punctuation, `&` versus `and` and a leading
+/// "The" don't matter.
+fn normalize(title: &str) -> String {
+    let title = title.replace('&', " and ");
+    let words: Vec<_> = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    match words.split_first() {
+        Some((first, rest)) if first == "the" && !rest.is_empty() => rest.join(" "),
+        _ => words.join(" "),
+    }
+}
+
+/// A lookup table from full journal titles to their abbreviations
+#[derive(Debug, Clone, Default)]
+pub struct Table(HashMap<String, String>);
+
+impl Table {
+    fn insert(&mut self, title: &str, abbrev: String) {
+        self.0.insert(normalize(title), abbrev);
+    }
+
+    /// Load a table in JabRef's format, with one `Full Title;Abbreviation` pair per line.  Any
+    /// further `;`-separated columns, blank lines and lines starting with `#` are ignored.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).context_read(path)?;
+        let mut t = Table::default();
+        for (n, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut cols = line.split(';').map(|c| c.trim().trim_matches('"'));
+            match (cols.next(), cols.next()) {
+                (Some(title), Some(abbrev)) if !title.is_empty() && !abbrev.is_empty() => {
+                    t.insert(title, abbrev.to_string())
+                }
+                _ => bail!(
+                    "{}:{}: expected `Full Title;Abbreviation`",
+                    path.display(),
+                    n + 1
+                ),
+            }
+        }
+        Ok(t)
+    }
+
+    /// Look up the abbreviation for `title` in this table, then the bundled one.
+    pub fn abbreviate(&self, title: &str) -> Option<&str> {
+        let key = normalize(title);
+        self.0
+            .get(&key)
+            .or_else(|| BUNDLED_TABLE.0.get(&key))
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abbreviate() {
+        let t = Table::default();
+        assert_eq!(t.abbreviate("Operations Research"), Some("Oper. Res."));
+        assert_eq!(t.abbreviate("OPERATIONS RESEARCH"), Some("Oper. Res."));
+        assert_eq!(
+            t.abbreviate("Computers and Operations Research"),
+            Some("Comput. Oper. Res.")
+        );
+        assert_eq!(
+            t.abbreviate("The American Economic Review"),
+            Some("Am. Econ. Rev.")
+        );
+        assert_eq!(t.abbreviate("Journal of Made Up Results"), None);
+    }
+}
@@ -2,13 +2,15 @@ use anyhow::Context;
 use jsonschema::JSONSchema;
 use lazy_static::lazy_static;
 use posix_cli_utils::{ArgEnum, Args, IoContext, Parser};
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use serde_json::Value as JsonValue;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tex_tools::*;
 
+pub mod arxiv_categories;
 pub mod convert;
 pub mod csl_fields;
+pub mod journal_abbreviations;
 
 type CslEntry = serde_json::Map<String, JsonValue>;
 
@@ -24,10 +26,7 @@ fn json_type_name(s: &JsonValue) -> &'static str {
 }
 
 trait JsonExt {
-    fn unwrap_array(self) -> Vec<JsonValue>;
     fn unwrap_object(self) -> serde_json::Map<String, JsonValue>;
-    fn unwrap_string(self) -> String;
-    fn unwrap_str(&self) -> &str;
 
     fn expect_string(self) -> Result<String>;
     fn expect_object(self) -> Result<serde_json::Map<String, JsonValue>>;
@@ -38,15 +37,6 @@ trait JsonExt {
 }
 
 impl JsonExt for JsonValue {
-    #[track_caller]
-    #[inline]
-    fn unwrap_array(self) -> Vec<JsonValue> {
-        match self {
-            JsonValue::Array(arr) => arr,
-            other => panic!("expected JSON array: {}", other),
-        }
-    }
-
     #[track_caller]
     #[inline]
     fn unwrap_object(self) -> serde_json::Map<String, JsonValue> {
@@ -56,24 +46,6 @@ impl JsonExt for JsonValue {
         }
     }
 
-    #[track_caller]
-    #[inline]
-    fn unwrap_string(self) -> String {
-        match self {
-            JsonValue::String(v) => v,
-            other => panic!("expected string: {}", other),
-        }
-    }
-
-    #[track_caller]
-    #[inline]
-    fn unwrap_str(&self) -> &str {
-        match self {
-            JsonValue::String(v) => v.as_str(),
-            other => panic!("expected string: {}", other),
-        }
-    }
-
     fn expect_string(self) -> Result<String> {
         match self {
             JsonValue::String(s) => Ok(s),
@@ -122,17 +94,36 @@ lazy_static! {
 
 fn compile_schema(s: &str) -> JSONSchema {
     let json: JsonValue = serde_json::from_str(s).expect("schema is not valid JSON");
+    compile_schema_json(&json).unwrap()
+}
+
+fn compile_schema_json(json: &JsonValue) -> Result<JSONSchema> {
     jsonschema::JSONSchema::options()
         .with_draft(jsonschema::Draft::Draft7)
-        .compile(&json)
-        .unwrap()
+        .compile(json)
+        .map_err(|e| anyhow!("invalid schema: {}", e))
+}
+
+/// Load a user-supplied Draft 7 JSON schema
+fn load_schema(path: impl AsRef<std::path::Path>) -> Result<JSONSchema> {
+    let path = path.as_ref();
+    let json: JsonValue = read_json(path)?;
+    compile_schema_json(&json).with_context(|| format!("failed to compile {}", path.display()))
 }
 
 mod cache {
-    use std::collections::HashMap;
+    use std::collections::{hash_map, HashMap};
+    use std::io::Write;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde::de::DeserializeOwned;
+    use serde::Deserialize;
 
     use super::*;
 
+    /// How long a failed fetch is remembered before the DOI is requested again.
+    const FAILED_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
     fn cache_dir() -> Result<PathBuf> {
         let mut path =
             dirs::cache_dir().ok_or_else(|| anyhow!("failed to find user cache directory"))?;
@@ -149,67 +140,410 @@ mod cache {
         Ok(path)
     }
 
-    #[derive(Debug, Deserialize, Serialize)]
-    pub struct FetchCache(HashMap<String, CslEntry>);
+    fn failed_cache() -> Result<PathBuf> {
+        let mut path = cache_dir()?;
+        path.push("fetch-failed.json");
+        Ok(path)
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    }
+
+    /// Format a Unix timestamp as a UTC `YYYY-MM-DD` date
+    fn format_date(secs: u64) -> String {
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = (secs / 86400) as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+
+    fn format_size(bytes: u64) -> String {
+        const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+        let mut size = bytes as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit + 1 < UNITS.len() {
+            size /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} B", bytes)
+        } else {
+            format!("{:.1} {}", size, UNITS[unit])
+        }
+    }
+
+    fn load_map<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+        // FIXME: could add compression here
+        if path.exists() {
+            std::fs::File::open(path)
+                .context_read(path)
+                .and_then(|f| serde_json::from_reader(f).context("corrupt JSON data"))
+        } else {
+            Ok(T::default())
+        }
+    }
+
+    fn save_map<T: Serialize>(path: &Path, map: &T) -> Result<()> {
+        let f = std::fs::File::create(path).context_write(path)?;
+        serde_json::to_writer(f, map)?;
+        Ok(())
+    }
+
+    /// DOIs are case-insensitive, so the cache is keyed by their lowercase form.
+    pub fn normalize_doi(doi: &str) -> String {
+        doi.trim().to_lowercase()
+    }
+
+    /// A cached entry and when it was fetched (in seconds since the Unix epoch)
+    #[derive(Debug, Clone, Deserialize, Serialize)]
+    #[serde(from = "StoredEntry")]
+    struct Cached {
+        fetched: u64,
+        entry: CslEntry,
+    }
+
+    /// Caches written before fetch times were recorded map DOIs straight to entries.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredEntry {
+        Cached { fetched: u64, entry: CslEntry },
+        Legacy(CslEntry),
+    }
+
+    impl From<StoredEntry> for Cached {
+        fn from(s: StoredEntry) -> Self {
+            match s {
+                StoredEntry::Cached { fetched, entry } => Cached { fetched, entry },
+                StoredEntry::Legacy(entry) => Cached { fetched: 0, entry },
+            }
+        }
+    }
+
+    /// The in-memory cache, which is also the format written by `cache export`
+    #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+    pub struct FetchCache {
+        entries: HashMap<String, Cached>,
+        /// DOIs which could not be fetched, and when (in seconds since the Unix epoch)
+        #[serde(default)]
+        failed: HashMap<String, u64>,
+    }
 
     impl FetchCache {
         pub fn clear() -> Result<()> {
-            let path = fetch_cache()?;
-            if path.exists() {
-                std::fs::remove_file(&path)?;
+            for path in [fetch_cache()?, failed_cache()?] {
+                if path.exists() {
+                    std::fs::remove_file(&path)?;
+                }
             }
             Ok(())
         }
 
         pub fn load() -> Result<Self> {
-            // FIXME: could add compression here
-            let path = fetch_cache()?;
-            if path.exists() {
-                std::fs::File::open(&path)
-                    .context_read(&path)
-                    .and_then(|f| serde_json::from_reader(f).context("corrupt JSON data"))
-            } else {
-                Ok(FetchCache::empty())
-            }
+            let stored = FetchCache {
+                entries: load_map(&fetch_cache()?)?,
+                failed: load_map(&failed_cache()?)?,
+            };
+            // Older caches may have mixed-case keys
+            let mut cache = FetchCache::default();
+            cache.merge(stored);
+            let expired = now().saturating_sub(FAILED_TTL.as_secs());
+            cache.failed.retain(|_, t| *t > expired);
+            Ok(cache)
         }
 
         pub fn save(&self) -> Result<()> {
-            let path = fetch_cache()?;
-            let f = std::fs::File::create(&path).context_write(&path)?;
-            serde_json::to_writer(f, &self)?;
-            Ok(())
-        }
-
-        pub fn empty() -> Self {
-            FetchCache(Default::default())
+            save_map(&fetch_cache()?, &self.entries)?;
+            save_map(&failed_cache()?, &self.failed)
         }
 
         pub fn get(&self, doi: &str) -> Option<&CslEntry> {
-            self.0.get(doi)
+            self.entries.get(&normalize_doi(doi)).map(|c| &c.entry)
         }
 
         pub fn contains(&self, doi: &str) -> bool {
-            self.0.contains_key(doi)
+            self.entries.contains_key(&normalize_doi(doi))
+        }
+
+        pub fn insert(&mut self, doi: &str, value: CslEntry) {
+            let doi = normalize_doi(doi);
+            self.failed.remove(&doi);
+            let cached = Cached {
+                fetched: now(),
+                entry: value,
+            };
+            self.entries.insert(doi, cached);
+        }
+
+        /// Whether fetching `doi` failed within the last [`FAILED_TTL`]
+        pub fn failed_recently(&self, doi: &str) -> bool {
+            self.failed.contains_key(&normalize_doi(doi))
+        }
+
+        pub fn insert_failed(&mut self, doi: &str) {
+            self.failed.insert(normalize_doi(doi), now());
         }
 
-        pub fn insert(&mut self, doi: String, value: CslEntry) {
-            self.0.insert(doi, value);
+        /// Merge `other` into this cache, keeping the most recently fetched copy of each entry.
+        /// Returns the number of entries which were added or replaced.
+        pub fn merge(&mut self, other: FetchCache) -> usize {
+            let mut count = 0;
+            for (doi, new) in other.entries {
+                match self.entries.entry(normalize_doi(&doi)) {
+                    hash_map::Entry::Occupied(mut e) => {
+                        if new.fetched > e.get().fetched {
+                            e.insert(new);
+                            count += 1;
+                        }
+                    }
+                    hash_map::Entry::Vacant(e) => {
+                        e.insert(new);
+                        count += 1;
+                    }
+                }
+            }
+            for (doi, t) in other.failed {
+                let last = self.failed.entry(normalize_doi(&doi)).or_insert(t);
+                *last = t.max(*last);
+            }
+            self.failed.retain(|doi, _| !self.entries.contains_key(doi));
+            count
         }
 
         #[allow(dead_code)]
         pub fn into_inner(self) -> HashMap<String, CslEntry> {
-            self.0
+            self.entries
+                .into_iter()
+                .map(|(doi, c)| (doi, c.entry))
+                .collect()
+        }
+    }
+
+    #[derive(clap::Subcommand)]
+    pub enum CacheCmd {
+        /// Write the fetch cache to a file, e.g. to share it with another machine
+        Export {
+            /// Output path
+            path: PathBuf,
+        },
+
+        /// Merge a file written by `cache export` into the fetch cache, keeping the most recently
+        /// fetched copy of each entry
+        Import {
+            /// Input path. Use "-" for STDIN.
+            path: PathBuf,
+        },
+
+        /// Show where the cache is stored, its size and what it contains
+        Info,
+
+        /// Print the cached entry for a DOI
+        Show {
+            /// The DOI to look up (case-insensitive)
+            doi: String,
+        },
+    }
+
+    fn show(doi: &str) -> Result<()> {
+        let cache = FetchCache::load()?;
+        let entry = match cache.get(doi) {
+            Some(entry) => entry,
+            None if cache.failed_recently(doi) => {
+                let failed = cache.failed[&normalize_doi(doi)];
+                bail!(
+                    "DOI `{}` is not cached, fetching it failed on {}",
+                    doi,
+                    format_date(failed)
+                )
+            }
+            None => bail!("DOI `{}` is not cached", doi),
+        };
+        let out = std::io::stdout();
+        let mut out = out.lock();
+        serde_json::to_writer_pretty(&mut out, entry)?;
+        writeln!(out)?;
+        Ok(())
+    }
+
+    fn info() -> Result<()> {
+        let cache = FetchCache::load()?;
+        let now = now();
+        let describe = |t: u64| {
+            format!(
+                "{} ({} days ago)",
+                format_date(t),
+                (now - t.min(now)) / 86400
+            )
+        };
+
+        for (label, path) in [
+            ("Cache file:", fetch_cache()?),
+            ("Failures file:", failed_cache()?),
+        ] {
+            let size = match std::fs::metadata(&path) {
+                Ok(m) => format_size(m.len()),
+                Err(_) => "not created yet".to_string(),
+            };
+            println!("{:<16}{} ({})", label, path.display(), size);
+        }
+        println!("{:<16}{}", "Cached DOIs:", cache.entries.len());
+
+        let times = cache.entries.values().map(|c| c.fetched).filter(|&t| t > 0);
+        if let (Some(oldest), Some(newest)) = (times.clone().min(), times.max()) {
+            println!("{:<16}{}", "Oldest entry:", describe(oldest));
+            println!("{:<16}{}", "Newest entry:", describe(newest));
+        }
+        let undated = cache.entries.values().filter(|c| c.fetched == 0).count();
+        if undated > 0 {
+            println!("{:<16}{} (cached by an older version)", "Undated:", undated);
+        }
+        println!(
+            "{:<16}{} (retried after {} days, or with `fetch --retry-failed`)",
+            "Failed DOIs:",
+            cache.failed.len(),
+            FAILED_TTL.as_secs() / 86400
+        );
+        Ok(())
+    }
+
+    pub fn main(cmd: CacheCmd) -> Result<()> {
+        match cmd {
+            CacheCmd::Export { path } => {
+                let cache = FetchCache::load()?;
+                write_json(&path, &cache)?;
+                info!(count = cache.entries.len(), path=?path, "exported cache");
+            }
+            CacheCmd::Import { path } => {
+                let imported: FetchCache = read_json(&path)?;
+                let mut cache = FetchCache::load()?;
+                let count = cache.merge(imported);
+                cache.save()?;
+                info!(count, "imported cache entries");
+            }
+            CacheCmd::Info => info()?,
+            CacheCmd::Show { doi } => show(&doi)?,
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn doi_case_insensitive() {
+            let mut cache = FetchCache::default();
+            let mut entry = CslEntry::new();
+            entry.insert("DOI".into(), "10.1000/XYZ".into());
+            cache.insert("10.1000/XYZ", entry);
+            assert!(cache.contains("10.1000/xyz"));
+            assert!(cache.get("10.1000/Xyz").is_some());
+
+            let mut imported = FetchCache::default();
+            imported.insert_failed("10.1000/ABC");
+            cache.merge(imported);
+            assert!(cache.failed_recently("10.1000/abc"));
+            cache.insert("10.1000/abc", CslEntry::new());
+            assert!(!cache.failed_recently("10.1000/ABC"));
+        }
+
+        #[test]
+        fn refresh() {
+            let entry = |title: &str| CslEntry::from_iter([("title".to_string(), title.into())]);
+            let mut stored = FetchCache::default();
+            for doi in ["10.1000/a", "10.1000/b"] {
+                let cached = Cached {
+                    fetched: 0,
+                    entry: entry("stale"),
+                };
+                stored.entries.insert(doi.into(), cached);
+            }
+            let mut fresh = FetchCache::default();
+            fresh.insert("10.1000/A", entry("fresh"));
+            fresh.insert_failed("10.1000/b");
+            assert_eq!(stored.merge(fresh), 1);
+            assert_eq!(stored.get("10.1000/a").unwrap()["title"], "fresh");
+            assert_eq!(stored.get("10.1000/b").unwrap()["title"], "stale");
+            assert!(!stored.failed_recently("10.1000/b"));
+        }
+
+        #[test]
+        fn format_date() {
+            use super::format_date as fmt;
+            assert_eq!(fmt(0), "1970-01-01");
+            assert_eq!(fmt(951_782_400), "2000-02-29");
+            assert_eq!(fmt(1_792_155_215), "2026-10-16");
+        }
+
+        #[test]
+        fn format_size() {
+            use super::format_size as fmt;
+            assert_eq!(fmt(1000), "1000 B");
+            assert_eq!(fmt(1536), "1.5 KiB");
+            assert_eq!(fmt(5 * 1024 * 1024), "5.0 MiB");
         }
     }
 }
 
+mod check_cites;
+mod color;
+mod dois;
 mod example;
 mod fetch;
+mod update;
 mod validate;
 mod output {}
 
+const EXIT_CODES_HELP: &str = "EXIT STATUS:
+    0    Success
+    1    The input failed validation or conversion
+    2    An IO or JSON parsing error occurred
+    3    A network error occurred, or some DOIs could not be fetched (with --check)";
+
+mod exit_code {
+    pub const INVALID: i32 = 1;
+    pub const IO: i32 = 2;
+    pub const NETWORK: i32 = 3;
+}
+
+fn exit_code(err: &anyhow::Error) -> i32 {
+    for cause in err.chain() {
+        if cause.is::<validate::ValidationFailed>() {
+            return exit_code::INVALID;
+        }
+        if cause.is::<fetch::FetchFailed>() || cause.is::<reqwest::Error>() {
+            return exit_code::NETWORK;
+        }
+        if cause.is::<std::io::Error>() || cause.is::<serde_json::Error>() {
+            return exit_code::IO;
+        }
+    }
+    exit_code::INVALID
+}
+
 #[derive(Parser)]
-#[clap(infer_subcommands(true))]
+#[clap(infer_subcommands(true), after_help = EXIT_CODES_HELP)]
+struct Cli {
+    /// Also write warnings and errors to this file, to review after the run
+    #[clap(long, global = true, value_name = "PATH")]
+    warnings_file: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(clap::Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum Cmd {
     /// Fetch missing bibliographic information from doi.org
     Fetch(fetch::ClArgs),
@@ -224,20 +558,49 @@ enum Cmd {
     /// Empty the request cache
     ClearCache,
 
+    /// Inspect, export or import the request cache
+    #[clap(subcommand)]
+    Cache(cache::CacheCmd),
+
+    /// Check that every key cited in LaTeX sources is in the database
+    CheckCites(check_cites::ClArgs),
+
     /// Print an example database entry
     Example(example::ClArgs),
+
+    /// Check that the bundled examples convert to their expected BibLaTeX
+    #[clap(hide = true)]
+    SelfTest,
+
+    /// Rewrite the expected BibLaTeX (.bib) of each CSL JSON (.json) example in a directory, for
+    /// when the output changes on purpose
+    #[clap(hide = true)]
+    Bless(example::BlessArgs),
 }
 
-fn main() -> Result<()> {
+fn main() {
     posix_cli_utils::reset_sigpipe();
-    logging_init();
+    let cli = Cli::parse();
+    if let Err(err) = logging_init_with_warnings_file(cli.warnings_file.as_deref()) {
+        eprintln!("Error: {:?}", err);
+        std::process::exit(exit_code(&err));
+    }
 
-    match Cmd::parse() {
+    let result = match cli.cmd {
         Cmd::Validate(args) => validate::main(args),
         #[cfg(debug_assertions)]
         Cmd::ValidateCache(args) => validate::validate_cache(args),
         Cmd::Fetch(args) => fetch::main(args),
         Cmd::ClearCache => cache::FetchCache::clear(),
+        Cmd::Cache(cmd) => cache::main(cmd),
+        Cmd::CheckCites(args) => check_cites::main(args),
         Cmd::Example(args) => example::main(args),
+        Cmd::SelfTest => example::self_test(),
+        Cmd::Bless(args) => example::bless(args),
+    };
+
+    if let Err(err) = result {
+        eprintln!("Error: {:?}", err);
+        std::process::exit(exit_code(&err));
     }
 }
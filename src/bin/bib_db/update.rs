@@ -0,0 +1,169 @@
+//! Incremental BibLaTeX output for `fetch --update`.  The BibLaTeX for each entry is saved in a
+//! manifest next to the output file, along with a hash of the CSL it was converted from.  On the
+//! next run, entries whose CSL hasn't changed are copied from the manifest instead of being
+//! converted again.
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+
+use super::*;
+use serde::Deserialize;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// Hash of [`convert::ConvertOptions::fingerprint`] for the run which wrote the manifest
+    fingerprint: String,
+    entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ManifestEntry {
+    id: String,
+    /// Hash of the CSL entry
+    hash: String,
+    biblatex: String,
+}
+
+/// The manifest for `output`, e.g. `refs.bib.manifest.json` for `refs.bib`
+pub fn manifest_path(output: &Path) -> PathBuf {
+    let mut name = output.file_name().expect("no file name").to_os_string();
+    name.push(".manifest.json");
+    output.with_file_name(name)
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable between Rust versions.
+fn hash(bytes: &[u8]) -> String {
+    let h = bytes.iter().fold(0xcbf29ce484222325_u64, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", h)
+}
+
+impl Manifest {
+    /// Load the manifest for `output`.  A missing or unreadable manifest, or one written with
+    /// different options, is treated as empty so every entry is converted.
+    fn load(output: &Path, fingerprint: &str) -> Manifest {
+        let path = manifest_path(output);
+        if !output.exists() || !path.exists() {
+            info!(path=%path.display(), "no previous output, converting all entries");
+            return Manifest::default();
+        }
+        match read_json::<Manifest, _>(&path) {
+            Ok(m) if m.fingerprint == fingerprint => m,
+            Ok(_) => {
+                info!("conversion options have changed, converting all entries");
+                Manifest::default()
+            }
+            Err(err) => {
+                warn!(path=%path.display(), "ignoring unreadable manifest: {:#}", err);
+                Manifest::default()
+            }
+        }
+    }
+}
+
+/// Like `output_biblatex`, but only convert the entries which changed since the last run.
+pub fn output_biblatex(
+    db: Vec<CslEntry>,
+    path: &Path,
+    ignore_errors: bool,
+    options: &convert::ConvertOptions,
+) -> Result<()> {
+    let fingerprint = hash(options.fingerprint()?.as_bytes());
+    let mut previous: HashMap<_, _> = Manifest::load(path, &fingerprint)
+        .entries
+        .into_iter()
+        .map(|e| (e.id.clone(), e))
+        .collect();
+
+    let mut manifest = Manifest {
+        fingerprint,
+        entries: Vec::with_capacity(db.len()),
+    };
+    let mut seen = HashSet::new();
+    let mut count_converted = 0;
+    for e in db {
+        let id = match e.get(csl_fields::ID).and_then(JsonValue::as_str) {
+            Some(id) => id.to_string(),
+            None => bail!("entry is missing an `id`"),
+        };
+        if !seen.insert(id.clone()) {
+            warn!(id = %id, "skipping entry with duplicate key");
+            continue;
+        }
+        let hash = hash(serde_json::to_string(&fetch::canonical_order(e.clone()))?.as_bytes());
+        match previous.remove(&id) {
+            Some(prev) if prev.hash == hash => manifest.entries.push(prev),
+            _ => {
+                let converted = convert::csl_to_biblatex(e, options).and_then(|e| {
+                    let mut biblatex = String::new();
+                    let e = e
+                        .with_field_names(options.field_names())
+                        .on_unknown(options.unknown_char_policy());
+                    write!(biblatex, "{}", e)
+                        .map_err(|_| anyhow!("failed to write entry `{}` as BibLaTeX", id))?;
+                    Ok(biblatex)
+                });
+                let biblatex = match converted {
+                    Ok(biblatex) => biblatex,
+                    Err(e) if !ignore_errors => return Err(e),
+                    Err(_) => continue,
+                };
+                count_converted += 1;
+                manifest.entries.push(ManifestEntry { id, hash, biblatex });
+            }
+        }
+    }
+    info!(
+        count_converted,
+        count_unchanged = manifest.entries.len() - count_converted,
+        "updating output"
+    );
+
+    let mut bib = String::new();
+    for e in &manifest.entries {
+        bib += &e.biblatex;
+        bib.push('\n');
+    }
+    std::fs::write(path, bib).context_write(path)?;
+    write_json(manifest_path(path), &manifest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changed_entries_are_converted() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("refs.bib");
+        let options = convert::ConvertOptions::default();
+
+        let mut db: Vec<CslEntry> = ["article", "book"]
+            .iter()
+            .map(|name| {
+                read_json(format!(
+                    "{}/tests/biblatex/{}.json",
+                    env!("CARGO_MANIFEST_DIR"),
+                    name
+                ))
+            })
+            .collect::<Result<_>>()?;
+        output_biblatex(db.clone(), &output, false, &options)?;
+        let full = std::fs::read_to_string(&output)?;
+
+        // An unchanged entry is copied from the manifest, so an edit to its BibLaTeX survives
+        let mut manifest: Manifest = read_json(manifest_path(&output))?;
+        manifest.entries[1].biblatex += "% unchanged";
+        write_json(manifest_path(&output), &manifest)?;
+        db[0].insert(csl_fields::TITLE.into(), "A new title".into());
+        output_biblatex(db, &output, false, &options)?;
+        let updated = std::fs::read_to_string(&output)?;
+
+        assert!(updated.contains("title = {A new title}"));
+        assert!(updated.contains("% unchanged"));
+        assert_ne!(full, updated);
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::Path;
 
 use super::*;
@@ -6,14 +7,68 @@ use serde_json::Value;
 
 #[derive(Args)]
 pub struct ClArgs {
-    /// Input file (CSL JSON format)
-    input: PathBuf,
+    /// Input files (CSL JSON format, or YAML if the extension is .yaml or .yml), or glob patterns
+    /// such as "refs/*.json".  Several files are validated as one database, so ids must be unique
+    /// across all of them.  A file may hold a single entry instead of an array.  Use "-" for STDIN,
+    /// which may be JSON or YAML.
+    #[clap(required = true)]
+    input: Vec<PathBuf>,
+
+    /// Validate entries against this JSON schema (Draft 7) instead of the built-in CSL schema
+    #[clap(long, value_name = "PATH")]
+    schema: Option<PathBuf>,
+
+    /// Error report format. The JSON report is an array of errors, written in addition to the log.
+    #[clap(arg_enum, short = 'f', long, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+
+    /// Output path for the JSON report. Default is STDOUT.
+    #[clap(short = 'o')]
+    output: Option<PathBuf>,
+
+    /// Treat warnings, such as fields which are not in the schema, as errors.
+    #[clap(long)]
+    strict: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// A harmless deviation from the schema, which only fails validation with `--strict`
+    Warning,
+    Error,
+}
+
+/// A single schema violation
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorRecord {
+    /// The input file containing the entry, if there are several
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// The index of the entry within its input file
+    pub entry_index: Option<usize>,
+    pub id: Option<JsonValue>,
+    pub json_path: String,
+    pub message: String,
+    pub severity: Severity,
 }
 
 fn try_find_id<'a>(entry: &'a Value) -> Option<&'a Value> {
     entry.as_object()?.get("id")
 }
 
+/// Whether `e` is harmless enough to be a warning rather than an error.  Currently these are
+/// fields which the schema doesn't define, which are ignored when converting.
+pub fn is_warning(e: &ValidationError) -> bool {
+    matches!(e.kind, ValidationErrorKind::AdditionalProperties { .. })
+}
+
 pub fn ignore_missing_type(e: &ValidationError) -> bool {
     match &e.kind {
         ValidationErrorKind::Required { property } => property.as_str() == Some("type"),
@@ -29,58 +84,318 @@ pub fn ignore_missing_id(e: &ValidationError) -> bool {
 }
 
 pub fn validate_entry(entry: &JsonValue, ignore: impl Fn(&ValidationError) -> bool) -> bool {
-    let errors = CSL_ENTRY_SCHEMA.validate(entry).err();
-
-    let mut errs = false;
-    if let Some(errors) = errors {
-        let id = try_find_id(&entry);
-
-        for error in errors.filter(|e| !ignore(e)) {
-            let span = error_span!(
-                "validate_entry",
-                id = tracing::field::Empty,
-                json_path = tracing::field::Empty,
-            );
-            if let Some(id) = id {
-                span.record("id", &tracing::field::display(id));
-            };
-            if error.instance_path.iter().next().is_some() {
-                span.record("json_path", &tracing::field::display(&error.instance_path));
-            }
-            let _s = span.enter();
+    validate_entry_against(&CSL_ENTRY_SCHEMA, entry, ignore)
+}
+
+pub fn validate_entry_against(
+    schema: &JSONSchema,
+    entry: &JsonValue,
+    ignore: impl Fn(&ValidationError) -> bool,
+) -> bool {
+    entry_errors(schema, entry, ignore, true).is_empty()
+}
+
+/// Validate a single entry, logging and returning the errors found.  Unless `strict` is set,
+/// errors for which [`is_warning`] holds are logged and recorded as warnings.
+pub fn entry_errors(
+    schema: &JSONSchema,
+    entry: &JsonValue,
+    ignore: impl Fn(&ValidationError) -> bool,
+    strict: bool,
+) -> Vec<ErrorRecord> {
+    let errors = match schema.validate(entry) {
+        Ok(()) => return Vec::new(),
+        Err(errors) => errors,
+    };
+    let id = try_find_id(&entry);
+
+    let mut records = Vec::new();
+    for error in errors.filter(|e| !ignore(e)) {
+        let span = error_span!(
+            "validate_entry",
+            id = tracing::field::Empty,
+            json_path = tracing::field::Empty,
+        );
+        if let Some(id) = id {
+            span.record("id", &tracing::field::display(id));
+        };
+        if error.instance_path.iter().next().is_some() {
+            span.record("json_path", &tracing::field::display(&error.instance_path));
+        }
+        let _s = span.enter();
+        let severity = if !strict && is_warning(&error) {
+            warn!("{}", error);
+            Severity::Warning
+        } else {
             error!("{}", error);
-            errs = true;
+            Severity::Error
+        };
+        records.push(ErrorRecord {
+            file: None,
+            entry_index: None,
+            id: id.cloned(),
+            json_path: error.instance_path.to_string(),
+            message: error.to_string(),
+            severity,
+        });
+    }
+    records
+}
+const EXIT_ERROR_MSG: &str = "Validation failed";
+
+/// Returned when the input fails schema validation, so `main` can tell it apart from IO errors.
+#[derive(Debug, Clone, Copy)]
+pub struct ValidationFailed;
+
+impl std::fmt::Display for ValidationFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(EXIT_ERROR_MSG)
+    }
+}
+
+impl std::error::Error for ValidationFailed {}
+
+fn validation_failed() -> anyhow::Error {
+    error!("{}", EXIT_ERROR_MSG);
+    ValidationFailed.into()
+}
+
+/// Expand any glob patterns in `inputs` which don't name an existing file, in case the shell
+/// didn't.  A pattern matching no files is an error.
+pub fn expand_inputs(inputs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let pattern = input.to_string_lossy();
+        if is_stdio(input) || input.exists() || !pattern.contains(['*', '?', '[']) {
+            paths.push(input.clone());
+            continue;
+        }
+        let n = paths.len();
+        for p in glob::glob(&pattern).with_context(|| format!("invalid pattern `{}`", pattern))? {
+            paths.push(p?);
+        }
+        if paths.len() == n {
+            bail!("no input files match `{}`", pattern);
         }
     }
+    Ok(paths)
+}
 
-    !errs
+/// The entries of one or more input files, concatenated
+struct Db {
+    entries: JsonValue,
+    /// Each input file and the index of its first entry in `entries`
+    files: Vec<(PathBuf, usize)>,
+}
+
+impl Db {
+    /// The input file and the index within it of `entries[entry_index]`.  The file is only
+    /// given if there are several.
+    fn locate(&self, entry_index: usize) -> (Option<&Path>, usize) {
+        if self.files.len() < 2 {
+            return (None, entry_index);
+        }
+        let (path, start) = self
+            .files
+            .iter()
+            .rev()
+            .find(|(_, start)| *start <= entry_index)
+            .unwrap();
+        (Some(path), entry_index - start)
+    }
+
+    /// Set the file and entry index of `record` from its index in the concatenated entries
+    fn locate_record(&self, record: &mut ErrorRecord) {
+        if let Some(i) = record.entry_index {
+            let (file, i) = self.locate(i);
+            record.file = file.map(Path::to_path_buf);
+            record.entry_index = Some(i);
+        }
+    }
+
+    fn span(&self, entry_index: usize) -> tracing::Span {
+        match self.locate(entry_index) {
+            (Some(file), entry_index) => {
+                error_span!("validate_db", file = %file.display(), entry_index)
+            }
+            (None, entry_index) => error_span!("validate_db", entry_index),
+        }
+    }
+}
+
+/// Read the entries of one input file.  The top-level value is normally an array of entries,
+/// but a single entry (an object) is also accepted.
+fn read_entries(path: &Path) -> Result<JsonValue> {
+    match read_json_or_yaml(path)? {
+        JsonValue::Object(entry) => {
+            debug!(path=%path.display(), "input file holds a single entry");
+            Ok(JsonValue::Array(vec![JsonValue::Object(entry)]))
+        }
+        entries => Ok(entries),
+    }
+}
+
+fn load_db(paths: &[impl AsRef<Path>]) -> Result<Db> {
+    if let [path] = paths {
+        let entries = read_entries(path.as_ref())?;
+        let files = vec![(path.as_ref().to_path_buf(), 0)];
+        return Ok(Db { entries, files });
+    }
+
+    let mut entries = Vec::new();
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let path = path.as_ref();
+        files.push((path.to_path_buf(), entries.len()));
+        let db = read_entries(path)?.expect_array().with_context(|| {
+            format!(
+                "top-level JSON value of {} must be an array or an object",
+                path.display()
+            )
+        })?;
+        info!(path=%path.display(), n_entries = db.len(), "read input file");
+        entries.extend(db);
+    }
+    Ok(Db {
+        entries: JsonValue::Array(entries),
+        files,
+    })
+}
+
+/// Validate the database as a whole against the bib_db schema, and check that no two entries
+/// share an `id`.  Errors about a particular entry have its `entry_index` set, as an index into
+/// the concatenated entries, and a `json_path` relative to the entry.
+fn db_errors(db: &Db) -> Vec<ErrorRecord> {
+    let mut records = {
+        let _s = error_span!("validate_db").entered();
+        entry_errors(&BIB_DB_SCHEMA, &db.entries, |_| false, true)
+    };
+    for record in &mut records {
+        let mut path = record.json_path.splitn(3, '/').skip(1);
+        record.entry_index = path.next().and_then(|i| i.parse().ok());
+        if record.entry_index.is_some() {
+            record.json_path = path.next().map(|p| format!("/{}", p)).unwrap_or_default();
+        }
+    }
+
+    let entries = match db.entries.as_array() {
+        Some(entries) => entries,
+        None => return records,
+    };
+    let mut first_index = HashMap::new();
+    for (entry_index, entry) in entries.iter().enumerate() {
+        let id = match try_find_id(entry) {
+            Some(id) => id,
+            None => continue,
+        };
+        if let Some(&first) = first_index.get(&id.to_string()) {
+            let _s = db.span(entry_index).entered();
+            let message = match db.locate(first) {
+                (Some(file), first) => format!(
+                    "duplicate id, first used by entry {} of {}",
+                    first,
+                    file.display()
+                ),
+                (None, first) => format!("duplicate id, first used by entry {}", first),
+            };
+            error!("{}", message);
+            records.push(ErrorRecord {
+                file: None,
+                entry_index: Some(entry_index),
+                id: Some(id.clone()),
+                json_path: "/id".into(),
+                message,
+                severity: Severity::Error,
+            });
+        } else {
+            first_index.insert(id.to_string(), entry_index);
+        }
+    }
+    records
+}
+
+/// Load and validate the entries of `paths`, concatenated into one database.  Warnings are
+/// logged but don't fail validation.
+pub fn load_and_validate_db(
+    paths: &[impl AsRef<Path>],
+    ignore_errors: bool,
+) -> Result<Vec<JsonValue>> {
+    load_and_validate_db_against(&CSL_ENTRY_SCHEMA, paths, ignore_errors, false)
 }
-const EXIT_ERROR_MSG: &str = "Validation failed";
 
-pub fn load_and_validate_db(path: impl AsRef<Path>, ignore_errors: bool) -> Result<Vec<JsonValue>> {
-    let mut db = read_json::<JsonValue, _>(path)?
-        .expect_array()
-        .context("top-level JSON value must be an array.")?;
+pub fn load_and_validate_db_against(
+    schema: &JSONSchema,
+    paths: &[impl AsRef<Path>],
+    ignore_errors: bool,
+    strict: bool,
+) -> Result<Vec<JsonValue>> {
+    let mut db = load_db(paths)?;
+    let db_errors = db_errors(&db);
+    if db_errors.iter().any(|e| e.entry_index.is_none()) {
+        return Err(validation_failed());
+    }
+    let mut entries = std::mem::take(&mut db.entries).expect_array()?;
 
     let mut all_valid = true;
     let mut entry_index = 0;
 
-    db.retain(|entry| {
-        let _s = error_span!("validate_db", entry_index).entered();
-        let valid = validate_entry(entry, ignore_missing_type);
+    entries.retain(|entry| {
+        let _s = db.span(entry_index).entered();
+        let valid = entry_errors(schema, entry, ignore_missing_type, strict)
+            .iter()
+            .all(|e| e.severity == Severity::Warning)
+            & !db_errors.iter().any(|e| e.entry_index == Some(entry_index));
         all_valid &= valid;
         entry_index += 1;
         valid
     });
 
     if !all_valid && !ignore_errors {
-        bail!("{}", EXIT_ERROR_MSG)
+        return Err(validation_failed());
+    }
+    Ok(entries)
+}
+
+fn json_report(schema: &JSONSchema, args: &ClArgs) -> Result<()> {
+    let db = load_db(&expand_inputs(&args.input)?)?;
+    let mut report = db_errors(&db);
+
+    for (entry_index, entry) in db.entries.as_array().into_iter().flatten().enumerate() {
+        let _s = db.span(entry_index).entered();
+        for mut record in entry_errors(schema, entry, ignore_missing_type, args.strict) {
+            record.entry_index = Some(entry_index);
+            report.push(record);
+        }
+    }
+    for record in &mut report {
+        db.locate_record(record);
+    }
+
+    match &args.output {
+        Some(path) => write_json_pretty(path, &report)?,
+        None => {
+            let out = std::io::stdout();
+            serde_json::to_writer_pretty(out.lock(), &report)?;
+        }
+    }
+
+    if report.iter().any(|e| e.severity == Severity::Error) {
+        return Err(validation_failed());
     }
-    Ok(db)
+    Ok(())
 }
 
 pub fn main(args: ClArgs) -> Result<()> {
-    load_and_validate_db(args.input, false)?;
+    let custom_schema = args.schema.as_ref().map(load_schema).transpose()?;
+    let schema = custom_schema.as_ref().unwrap_or(&CSL_ENTRY_SCHEMA);
+
+    match args.format {
+        ReportFormat::Text => {
+            let inputs = expand_inputs(&args.input)?;
+            load_and_validate_db_against(schema, &inputs, false, args.strict)?;
+        }
+        ReportFormat::Json => json_report(schema, &args)?,
+    }
     Ok(())
 }
 
@@ -114,6 +429,115 @@ pub fn validate_cache(options: ValidateCacheOptions) -> Result<()> {
     if ok {
         Ok(())
     } else {
-        bail!("{}", EXIT_ERROR_MSG)
+        Err(validation_failed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn single_file(entries: JsonValue) -> Db {
+        Db {
+            entries,
+            files: vec![("db.json".into(), 0)],
+        }
+    }
+
+    #[test]
+    fn db_level_errors() {
+        let db = single_file(json!([
+            { "id": "a", "type": "book" },
+            { "type": "book" },
+            { "id": "a", "type": "report" },
+        ]));
+        let errors: Vec<_> = db_errors(&db)
+            .into_iter()
+            .map(|e| (e.entry_index, e.json_path))
+            .collect();
+        assert_eq!(
+            errors,
+            [(Some(1), "".to_string()), (Some(2), "/id".to_string())]
+        );
+
+        let errors = db_errors(&single_file(json!({ "id": "a" })));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].entry_index, None);
+    }
+
+    #[test]
+    fn duplicates_across_files() {
+        let db = Db {
+            entries: json!([{ "id": "a" }, { "id": "b" }, { "id": "c" }, { "id": "a" }]),
+            files: vec![("x.json".into(), 0), ("y.json".into(), 2)],
+        };
+        let mut errors = db_errors(&db);
+        assert_eq!(errors.len(), 1);
+        db.locate_record(&mut errors[0]);
+        assert_eq!(errors[0].file.as_deref(), Some(Path::new("y.json")));
+        assert_eq!(errors[0].entry_index, Some(1));
+        assert_eq!(
+            errors[0].message,
+            "duplicate id, first used by entry 0 of x.json"
+        );
+    }
+
+    #[test]
+    fn warnings() -> Result<()> {
+        let entry = json!({ "id": "a", "type": "book", "unknown": 1, "title": 2 });
+        let severities = |strict| {
+            let mut s: Vec<_> = entry_errors(&CSL_ENTRY_SCHEMA, &entry, |_| false, strict)
+                .into_iter()
+                .map(|e| (e.json_path, e.severity))
+                .collect();
+            s.sort_by(|a, b| a.0.cmp(&b.0));
+            s
+        };
+        assert_eq!(
+            severities(false),
+            [
+                ("".to_string(), Severity::Warning),
+                ("/title".to_string(), Severity::Error)
+            ]
+        );
+        assert!(severities(true).iter().all(|(_, s)| *s == Severity::Error));
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("db.json");
+        write_json(&path, json!([{ "id": "a", "type": "book", "unknown": 1 }]))?;
+        let lenient = load_and_validate_db_against(&CSL_ENTRY_SCHEMA, &[&path], false, false);
+        let strict = load_and_validate_db_against(&CSL_ENTRY_SCHEMA, &[&path], false, true);
+        assert_eq!(lenient?.len(), 1);
+        assert!(strict.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn single_entry_files() -> Result<()> {
+        let dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/biblatex"));
+        let entries = load_and_validate_db(&[dir.join("article.json")], false)?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["id"], "barnhart1998");
+
+        let entries =
+            load_and_validate_db(&[dir.join("article.json"), dir.join("book.json")], false)?;
+        assert_eq!(entries.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn expand_glob() -> Result<()> {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/biblatex");
+        let pattern = PathBuf::from(format!("{}/*-thesis.json", dir));
+        let inputs = expand_inputs(&[pattern])?;
+        assert_eq!(inputs.len(), 2);
+        assert!(inputs
+            .iter()
+            .all(|p| p.to_string_lossy().ends_with("-thesis.json")));
+
+        let missing = PathBuf::from(format!("{}/*.missing", dir));
+        assert!(expand_inputs(&[missing]).is_err());
+        Ok(())
     }
 }
@@ -0,0 +1,47 @@
+use posix_cli_utils::{ArgEnum, Parser};
+use std::io::{Read, Write};
+use tex_tools::*;
+
+/// Escape UTF-8 text from STDIN for LaTeX, writing the result to STDOUT
+#[derive(Parser)]
+struct ClArgs {
+    /// What to do with characters which have no LaTeX equivalent
+    #[clap(arg_enum, long, default_value_t = OnUnknown::Error)]
+    on_unknown: OnUnknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum OnUnknown {
+    Passthrough,
+    Substitute,
+    Error,
+}
+
+impl From<OnUnknown> for UnknownCharPolicy {
+    fn from(o: OnUnknown) -> Self {
+        match o {
+            OnUnknown::Passthrough => UnknownCharPolicy::Passthrough,
+            OnUnknown::Substitute => UnknownCharPolicy::Substitute,
+            OnUnknown::Error => UnknownCharPolicy::Error,
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    posix_cli_utils::reset_sigpipe();
+    logging_init();
+    let args = ClArgs::parse();
+
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .context("failed to read STDIN")?;
+
+    let escaped = utf8_to_tex(&input).on_unknown(args.on_unknown.into());
+    if args.on_unknown == OnUnknown::Error {
+        escaped.check()?;
+    }
+    let stdout = std::io::stdout();
+    write!(stdout.lock(), "{}", escaped)?;
+    Ok(())
+}
@@ -0,0 +1,173 @@
+//! Helpers shared by the binaries: argument parsing, reading and writing JSON and YAML files, and
+//! logging.  Only available with the `bin` feature.
+
+use crate::*;
+use posix_cli_utils::IoContext;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{io::Read, path::Path};
+
+pub use clap::{Args, Parser};
+
+/// Read JSON from a file, or from STDIN if `path` is `-`.
+pub fn read_json<T, P>(path: P) -> Result<T>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if is_stdio(path) {
+        let stdin = std::io::stdin();
+        let val = serde_json::from_reader(stdin.lock())?;
+        return Ok(val);
+    }
+    let f = std::fs::File::open(path).context_read(&path)?;
+    let val = serde_json::from_reader(f)?;
+    Ok(val)
+}
+
+/// Read YAML from a file, or from STDIN if `path` is `-`.
+pub fn read_yaml<T, P>(path: P) -> Result<T>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if is_stdio(path) {
+        let stdin = std::io::stdin();
+        let val = serde_yaml::from_reader(stdin.lock())?;
+        return Ok(val);
+    }
+    let f = std::fs::File::open(path).context_read(path)?;
+    let val = serde_yaml::from_reader(f)?;
+    Ok(val)
+}
+
+/// Read YAML if `path` has a YAML extension (see [`is_yaml`]), and JSON otherwise.  STDIN
+/// (`-`) has no extension, so it is read as JSON if it starts with `{` or `[`, and YAML otherwise.
+pub fn read_json_or_yaml<T, P>(path: P) -> Result<T>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    if is_stdio(&path) {
+        let mut s = String::new();
+        std::io::stdin().read_to_string(&mut s)?;
+        return from_json_or_yaml_str(&s);
+    }
+    if is_yaml(&path) {
+        read_yaml(path)
+    } else {
+        read_json(path)
+    }
+}
+
+/// Parse `s` as JSON if it starts with `{` or `[`, and YAML otherwise.
+fn from_json_or_yaml_str<T: DeserializeOwned>(s: &str) -> Result<T> {
+    if s.trim_start().starts_with(['{', '[']) {
+        Ok(serde_json::from_str(s)?)
+    } else {
+        Ok(serde_yaml::from_str(s)?)
+    }
+}
+
+pub fn write_json<T, P>(path: P, val: T) -> Result<()>
+where
+    T: Serialize,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let f = std::fs::File::create(path).context_write(&path)?;
+    serde_json::to_writer(f, &val)?;
+    Ok(())
+}
+
+pub fn write_json_pretty<T, P>(path: P, val: T) -> Result<()>
+where
+    T: Serialize,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let f = std::fs::File::create(path).context_write(&path)?;
+    serde_json::to_writer_pretty(f, &val)?;
+    Ok(())
+}
+
+/// Write `val` as YAML to the file at `path`, replacing it if it exists.
+pub fn write_yaml<T, P>(path: P, val: T) -> Result<()>
+where
+    T: Serialize,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let f = std::fs::File::create(path).context_write(path)?;
+    serde_yaml::to_writer(f, &val)?;
+    Ok(())
+}
+
+/// Log to STDERR, filtered by `RUST_LOG`.  Set `TEX_TOOLS_LOG_FORMAT=json` to log JSON lines,
+/// with span fields included, instead of human-readable text.
+pub fn logging_init() {
+    logging_init_with_warnings_file(None).unwrap()
+}
+
+/// Like [`logging_init`], but also write `WARN` and `ERROR` events to `warnings_file`, if given,
+/// so they can be reviewed after a long run.  The file is overwritten.
+pub fn logging_init_with_warnings_file(warnings_file: Option<&Path>) -> Result<()> {
+    use std::sync::Mutex;
+    use tracing_subscriber::{filter::LevelFilter, fmt, prelude::*, EnvFilter};
+
+    let filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy();
+    let json = std::env::var("TEX_TOOLS_LOG_FORMAT").ok().as_deref() == Some("json");
+    let warnings = match warnings_file {
+        Some(path) => Some(std::fs::File::create(path).context_write(path)?),
+        None => None,
+    };
+    // Generic since the subscriber type differs between the formats below
+    fn warnings_layer<S>(f: std::fs::File) -> impl tracing_subscriber::Layer<S>
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fmt::layer()
+            .with_writer(Mutex::new(f))
+            .with_ansi(false)
+            .with_filter(LevelFilter::WARN)
+    }
+
+    if json {
+        tracing_subscriber::registry()
+            .with(fmt::layer().json().with_writer(std::io::stderr))
+            .with(warnings.map(warnings_layer))
+            .with(filter)
+            .init();
+    } else {
+        tracing_subscriber::registry()
+            .with(fmt::layer().with_writer(std::io::stderr).without_time())
+            .with(warnings.map(warnings_layer))
+            .with(filter)
+            .init();
+    }
+    Ok(())
+}
+
+pub fn logging_init_test() {
+    use tracing_subscriber::{fmt, prelude::*};
+    tracing_subscriber::fmt().without_time().try_init().ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn json_or_yaml_str() -> Result<()> {
+        let expected = json!([{ "id": "a", "title": "A" }]);
+        let json: Value = from_json_or_yaml_str(r#" [{"id": "a", "title": "A"}]"#)?;
+        assert_eq!(json, expected);
+        let yaml: Value = from_json_or_yaml_str("# comment\n- id: a\n  title: A\n")?;
+        assert_eq!(yaml, expected);
+        Ok(())
+    }
+}
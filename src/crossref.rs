@@ -0,0 +1,484 @@
+//! Clean up CSL-JSON as returned by Crossref, so it validates against the CSL schema.
+use serde_json::{Map, Value as JsonValue};
+
+use crate::{infer_csl_type, instrument, strip_markup, warn};
+
+/// Take the print value from a Crossref `isbn-type` or `issn-type` list
+fn pop_print_value(entry: &mut Map<String, JsonValue>, type_field: &str) -> Option<JsonValue> {
+    let kinds = match entry.remove(type_field)? {
+        JsonValue::Array(a) => a,
+        _ => return None,
+    };
+
+    for val in kinds {
+        let mut val = match val {
+            JsonValue::Object(m) => m,
+            _ => continue,
+        };
+
+        if val.get("type").and_then(JsonValue::as_str) == Some("print") {
+            return val.remove("value");
+        }
+    }
+    None
+}
+
+/// Fields which CSL defines as scalars, but Crossref may return as arrays
+pub const SCALAR_FIELDS: &[&str] = &[
+    "ISBN",
+    "ISSN",
+    "container-title",
+    "container-title-short",
+    "language",
+    "publisher",
+];
+
+/// Replace array values of `fields` with their first element, warning if others are discarded.
+/// An empty array is removed, like a missing field.
+pub fn collapse_arrays<'a>(
+    entry: &mut Map<String, JsonValue>,
+    fields: impl IntoIterator<Item = &'a str>,
+) {
+    for field in fields {
+        let first = match entry.get_mut(field) {
+            Some(JsonValue::Array(values)) => {
+                if values.len() > 1 {
+                    warn!(field, ?values, "keeping the first of several values");
+                }
+                values.drain(..).next()
+            }
+            _ => continue,
+        };
+        match first {
+            Some(v) => entry.insert(field.into(), v),
+            None => entry.remove(field),
+        };
+    }
+}
+
+/// Crossref strings may contain HTML entities (`&amp;`, `&#x2013;`) and markup (`<i>`, MathML)
+fn strip_markup_recursive(val: &mut JsonValue) {
+    match val {
+        JsonValue::String(s) => *s = strip_markup(s),
+        JsonValue::Array(values) => values.iter_mut().for_each(strip_markup_recursive),
+        JsonValue::Object(map) => map.values_mut().for_each(strip_markup_recursive),
+        _ => {}
+    }
+}
+
+fn clean_name_fields(author: &mut JsonValue) {
+    if let Some(fields) = author.as_object_mut() {
+        for drop_field in ["ORCID", "authenticated-orcid", "sequence", "affiliation"] {
+            fields.remove(drop_field);
+        }
+    }
+}
+
+/// Crossref fields which [`clean_crossref_entry`] removes
+pub const DROP_FIELDS: &[&str] = &[
+    "abstract",
+    "alternative-id",
+    "assertion",
+    "content-domain",
+    "copywrite",
+    "copyright",
+    "created",
+    "deposited",
+    "funder",
+    "indexed",
+    "is-referenced-by-count",
+    "journal-issue",
+    "license",
+    "link",
+    "member",
+    "prefix",
+    "published-online",
+    "published-print",
+    "published",
+    "publisher-location",
+    "reference-count",
+    "reference",
+    "references-count",
+    "relation",
+    "resource",
+    "score",
+    "short-title",
+    "subject",
+    "subtitle",
+    "update-policy",
+];
+
+/// Clean up an entry fetched from Crossref:
+///
+/// - the print ISBN and ISSN are taken from `isbn-type` and `issn-type`, and array-valued scalar
+///   fields (see [`SCALAR_FIELDS`]) are collapsed to their first value,
+/// - `article-number` becomes `number`,
+/// - the fields in [`DROP_FIELDS`] are removed, as are empty `original-title`s,
+/// - Crossref types are mapped to CSL types, e.g. `journal-article` to `article-journal`,
+/// - ORCIDs and affiliations are removed from authors,
+/// - markup is stripped from every field except the DOI and URL (see [`strip_markup`]).
+///
+/// Values which aren't JSON objects are left alone.
+pub fn clean_crossref_entry(entry: &mut JsonValue) {
+    clean_crossref_entry_with(entry, |f| DROP_FIELDS.contains(&f))
+}
+
+/// Like [`clean_crossref_entry`], but remove the fields for which `drop` returns `true` instead
+/// of [`DROP_FIELDS`].
+#[instrument(level = "error", name = "clean", skip(entry, drop))]
+pub fn clean_crossref_entry_with(entry: &mut JsonValue, drop: impl Fn(&str) -> bool) {
+    let entry = match entry.as_object_mut() {
+        Some(e) => e,
+        None => return,
+    };
+
+    for (field, type_field) in [("ISBN", "isbn-type"), ("ISSN", "issn-type")] {
+        if let Some(v) = pop_print_value(entry, type_field) {
+            entry.insert(field.into(), v);
+        }
+    }
+    collapse_arrays(entry, SCALAR_FIELDS.iter().copied());
+
+    // CSL has no `article-number`, but `number` serves the same purpose
+    if let Some(n) = entry.remove("article-number") {
+        if !entry.contains_key("number") {
+            entry.insert("number".into(), n);
+        }
+    }
+
+    let drop_fields: Vec<_> = entry.keys().filter(|f| drop(f)).cloned().collect();
+    for f in drop_fields {
+        entry.remove(&f);
+    }
+
+    for drop_if_empty in ["original-title"] {
+        let v = match entry.get(drop_if_empty) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let drop = match v {
+            JsonValue::Array(arr) => arr.is_empty(),
+            JsonValue::Object(map) => map.is_empty(),
+            JsonValue::Null => true,
+            _ => false,
+        };
+
+        if drop {
+            entry.remove(drop_if_empty);
+        }
+    }
+
+    if let Some(ty) = entry.get_mut("type") {
+        if let Some(new_ty) = ty.as_str().and_then(infer_csl_type) {
+            *ty = new_ty.into();
+        }
+    }
+
+    if let Some(authors) = entry.get_mut("author").and_then(JsonValue::as_array_mut) {
+        for author in authors {
+            clean_name_fields(author);
+        }
+    }
+
+    if entry.get("publisher").and_then(JsonValue::as_str) == Some("arXiv")
+        && !entry.contains_key("genre")
+    {
+        entry.insert("genre".into(), "arxiv".into());
+    }
+
+    for (field, val) in entry.iter_mut() {
+        // SICI-style DOIs contain `<` and `>`
+        if field != "DOI" && field != "URL" {
+            strip_markup_recursive(val);
+        }
+    }
+}
+
+/// Remove the `URL` if it just points to the `DOI`, like the `http://dx.doi.org/...` URLs
+/// Crossref returns.  Other URLs are kept.
+pub fn strip_doi_url(entry: &mut JsonValue) {
+    let entry = match entry.as_object_mut() {
+        Some(e) => e,
+        None => return,
+    };
+    let doi = match entry.get("DOI").and_then(JsonValue::as_str) {
+        Some(doi) if !doi.is_empty() => doi.to_ascii_lowercase(),
+        _ => return,
+    };
+    let is_doi_url = entry
+        .get("URL")
+        .and_then(JsonValue::as_str)
+        .and_then(|url| {
+            url.to_ascii_lowercase()
+                .strip_suffix(&doi)
+                .map(|p| p.ends_with('/'))
+        })
+        .unwrap_or(false);
+    if is_doi_url {
+        entry.remove("URL");
+    }
+}
+
+/// Titles Crossref returns for items with no real title of their own, compared ignoring case
+const PLACEHOLDER_TITLES: &[&str] = &[
+    "back matter",
+    "editorial board",
+    "front matter",
+    "index",
+    "n/a",
+    "no title",
+    "none",
+    "table of contents",
+    "title",
+    "title pending",
+    "tba",
+    "tbd",
+    "unknown",
+    "untitled",
+];
+
+/// Whether `c` belongs to a script written without spaces between words, such as Chinese,
+/// Japanese or Thai.
+fn is_unspaced_script(c: char) -> bool {
+    matches!(c,
+        '\u{0E00}'..='\u{0EFF}' // Thai, Lao
+        | '\u{0F00}'..='\u{0FFF}' // Tibetan
+        | '\u{1000}'..='\u{109F}' // Myanmar
+        | '\u{1780}'..='\u{17FF}' // Khmer
+        | '\u{3040}'..='\u{30FF}' // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Unified Ideographs Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        | '\u{20000}'..='\u{3134F}' // CJK Unified Ideographs Extensions B to G
+    )
+}
+
+/// Why `title` looks like incomplete metadata, if it does: it is a known placeholder (see
+/// [`PLACEHOLDER_TITLES`]), a single word, or all capitals.  Titles in scripts written without
+/// spaces are not counted in words, and titles in scripts without case are never all capitals.
+pub fn suspicious_title(title: &str) -> Option<&'static str> {
+    let trimmed = title
+        .trim()
+        .trim_matches(|c: char| c.is_ascii_punctuation() && c != '/');
+    if PLACEHOLDER_TITLES
+        .iter()
+        .any(|p| p.eq_ignore_ascii_case(trimmed))
+    {
+        return Some("placeholder title");
+    }
+    if trimmed.split_whitespace().count() < 2 && !trimmed.chars().any(is_unspaced_script) {
+        return Some("single-word title");
+    }
+    // Letters of caseless scripts, like Arabic or Chinese, are neither upper nor lower case
+    let cased = || {
+        trimmed
+            .chars()
+            .filter(|c| c.is_uppercase() || c.is_lowercase())
+    };
+    if cased().count() >= 4 && !cased().any(char::is_lowercase) {
+        return Some("all-caps title");
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cleaned(mut entry: JsonValue) -> JsonValue {
+        clean_crossref_entry(&mut entry);
+        entry
+    }
+
+    #[test]
+    fn type_remap() {
+        assert_eq!(
+            cleaned(json!({ "type": "journal-article" })),
+            json!({ "type": "article-journal" })
+        );
+        assert_eq!(
+            cleaned(json!({ "type": "proceedings-article" })),
+            json!({ "type": "paper-conference" })
+        );
+        assert_eq!(
+            cleaned(json!({ "type": "book" })),
+            json!({ "type": "book" })
+        );
+    }
+
+    #[test]
+    fn print_isbn() {
+        assert_eq!(
+            cleaned(json!({
+                "ISBN": ["9780000000002", "9780000000019"],
+                "isbn-type": [
+                    { "value": "9780000000002", "type": "electronic" },
+                    { "value": "9780000000019", "type": "print" },
+                ],
+            })),
+            json!({ "ISBN": "9780000000019" })
+        );
+        // Without a print value, the first is kept
+        assert_eq!(
+            cleaned(json!({
+                "ISBN": ["9780000000002", "9780000000019"],
+                "isbn-type": [{ "value": "9780000000002", "type": "electronic" }],
+            })),
+            json!({ "ISBN": "9780000000002" })
+        );
+    }
+
+    #[test]
+    fn collapse_arrays() {
+        assert_eq!(
+            cleaned(json!({
+                "ISSN": ["0030-364X", "1526-5463"],
+                "issn-type": [
+                    { "value": "1526-5463", "type": "electronic" },
+                    { "value": "0030-364X", "type": "print" },
+                ],
+                "ISBN": ["978-3-16-148410-0"],
+                "container-title": ["Operations Research"],
+                "container-title-short": [],
+                "publisher": ["INFORMS", "Institute for Operations Research"],
+                "type": "journal-article",
+            })),
+            json!({
+                "ISSN": "0030-364X",
+                "ISBN": "978-3-16-148410-0",
+                "container-title": "Operations Research",
+                "publisher": "INFORMS",
+                "type": "article-journal",
+            })
+        );
+
+        let mut entry = json!({ "title": ["A", "B"], "page": "1-2" });
+        super::collapse_arrays(entry.as_object_mut().unwrap(), ["title", "page", "missing"]);
+        assert_eq!(entry, json!({ "title": "A", "page": "1-2" }));
+    }
+
+    #[test]
+    fn drop_empty() {
+        for empty in [json!([]), json!({}), JsonValue::Null] {
+            assert_eq!(
+                cleaned(json!({ "title": "A", "original-title": empty })),
+                json!({ "title": "A" })
+            );
+        }
+        assert_eq!(
+            cleaned(json!({ "original-title": ["B"] })),
+            json!({ "original-title": ["B"] })
+        );
+    }
+
+    #[test]
+    fn drop_fields() {
+        assert_eq!(
+            cleaned(json!({
+                "title": "A",
+                "abstract": "<jats:p>An abstract</jats:p>",
+                "reference-count": 10,
+                "link": [],
+            })),
+            json!({ "title": "A" })
+        );
+
+        let mut entry = json!({ "title": "A", "abstract": "B", "page": "1-2" });
+        clean_crossref_entry_with(&mut entry, |f| f == "page");
+        assert_eq!(entry, json!({ "title": "A", "abstract": "B" }));
+    }
+
+    #[test]
+    fn article_number() {
+        assert_eq!(
+            cleaned(json!({ "article-number": "e12" })),
+            json!({ "number": "e12" })
+        );
+        assert_eq!(
+            cleaned(json!({ "article-number": "e12", "number": "3" })),
+            json!({ "number": "3" })
+        );
+    }
+
+    #[test]
+    fn authors_and_markup() {
+        assert_eq!(
+            cleaned(json!({
+                "title": "The <i>A</i> &amp; B",
+                "DOI": "10.1002/(sici)1097-0037(199712)30:4<255::aid-net5>3.0.co;2-8",
+                "author": [{
+                    "given": "Ada",
+                    "family": "Lovelace",
+                    "ORCID": "http://orcid.org/0000-0000-0000-0000",
+                    "sequence": "first",
+                    "affiliation": [],
+                }],
+                "publisher": "arXiv",
+            })),
+            json!({
+                "title": "The <i>A</i> & B",
+                "DOI": "10.1002/(sici)1097-0037(199712)30:4<255::aid-net5>3.0.co;2-8",
+                "author": [{ "given": "Ada", "family": "Lovelace" }],
+                "publisher": "arXiv",
+                "genre": "arxiv",
+            })
+        );
+    }
+
+    #[test]
+    fn doi_url() {
+        let strip = |mut entry: JsonValue| {
+            strip_doi_url(&mut entry);
+            entry
+        };
+        assert_eq!(
+            strip(
+                json!({ "DOI": "10.1287/OPRE.2019.1234", "URL": "http://dx.doi.org/10.1287/opre.2019.1234" })
+            ),
+            json!({ "DOI": "10.1287/OPRE.2019.1234" })
+        );
+        for url in [
+            "https://example.com/paper.pdf",
+            "https://example.com/x10.1287/opre.2019.1234",
+        ] {
+            let entry = json!({ "DOI": "10.1287/opre.2019.1234", "URL": url });
+            assert_eq!(strip(entry.clone()), entry);
+        }
+        let entry = json!({ "URL": "https://doi.org/" });
+        assert_eq!(strip(entry.clone()), entry);
+    }
+
+    #[test]
+    fn suspicious_titles() {
+        for (title, reason) in [
+            ("Untitled", "placeholder title"),
+            ("[No title]", "placeholder title"),
+            ("Front Matter", "placeholder title"),
+            ("Introduction", "single-word title"),
+            ("", "single-word title"),
+            ("THE TRAVELLING SALESMAN PROBLEM", "all-caps title"),
+            ("مقدمة", "single-word title"),
+        ] {
+            assert_eq!(suspicious_title(title), Some(reason), "{:?}", title);
+        }
+        for title in [
+            "The Travelling Salesman Problem",
+            "DNA repair",
+            "An index of papers",
+            "深層学習による画像認識",
+            "巡回セールスマン問題の近似解法",
+            "การวิเคราะห์ข้อมูล",
+            "تحليل البيانات الضخمة",
+            "DNA 修復の分子機構",
+        ] {
+            assert_eq!(suspicious_title(title), None, "{:?}", title);
+        }
+    }
+
+    #[test]
+    fn not_an_object() {
+        assert_eq!(cleaned(json!("x")), json!("x"));
+    }
+}
@@ -7,6 +7,21 @@ use unicode_normalization::UnicodeNormalization;
 ///  the escaped string.
 pub struct Utf8ToTex<'a> {
     original: &'a str,
+    on_unknown: UnknownCharPolicy,
+}
+
+/// What to do with characters which have no known LaTeX equivalent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownCharPolicy {
+    /// Write the character as UTF-8 and rely on biber and the TeX engine to handle it.
+    Passthrough,
+    /// Replace the character with `?`.
+    Substitute,
+    /// Formatting fails with [`std::fmt::Error`] (the default).  Use [`Utf8ToTex::check`] to
+    /// find out which character is the problem.  Beware that `to_string()` panics when formatting
+    /// fails, so use `write!` to escape strings which may contain such characters.
+    #[default]
+    Error,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -14,12 +29,15 @@ enum CharKind {
     Verbatim(char),
     Escape(&'static str),
     Combining(&'static str),
+    /// A combining character which spans the previous and the next glyph, like the tie in `\t{oo}`
+    CombiningDouble(&'static str),
 }
 
+/// Returns `None` if the character has no known LaTeX equivalent.
 #[instrument(level = "trace", name = "classify_utf8_char")]
-fn classify_char(c: char) -> CharKind {
+fn classify_char(c: char) -> Option<CharKind> {
     use CharKind::*;
-    match c {
+    let kind = match c {
         c if c.is_ascii_alphanumeric() | c.is_ascii_whitespace() => Verbatim(c),
         '\\' => Escape(r"\textbackslash{}"),
         '~' => Escape(r"\textasciitilde{}"),
@@ -36,6 +54,16 @@ fn classify_char(c: char) -> CharKind {
         'ø' => Escape(r"\o{}"),
         'Ø' => Escape(r"\O{}"),
         'ı' => Escape(r"{\i}"),
+        'æ' => Escape(r"\ae{}"),
+        'Æ' => Escape(r"\AE{}"),
+        'œ' => Escape(r"\oe{}"),
+        'Œ' => Escape(r"\OE{}"),
+        'ß' => Escape(r"\ss{}"),
+        'ẞ' => Escape(r"\SS{}"),
+        'đ' => Escape(r"\dj{}"),
+        'Đ' => Escape(r"\DJ{}"),
+        'þ' => Escape(r"\th{}"),
+        'Þ' => Escape(r"\TH{}"),
         '\u{0300}' => Combining(r"\`"),
         '\u{0301}' => Combining(r"\'"),
         '\u{0302}' => Combining(r"\^"),
@@ -50,6 +78,16 @@ fn classify_char(c: char) -> CharKind {
         '\u{0323}' => Combining(r"\d"),
         '\u{0327}' => Combining(r"\c"),
         '\u{0328}' => Combining(r"\k"),
+        '\u{0305}' => Combining(r"\="),
+        '\u{0309}' => Combining(r"\h"), // vietnam package
+        '\u{030f}' => Combining(r"\textdoublegrave"), // tipa package
+        '\u{0311}' => Combining(r"\textroundcap"), // tipa package
+        '\u{0324}' => Combining(r"\textsubumlaut"), // tipa package
+        '\u{0325}' => Combining(r"\textsubring"), // tipa package
+        '\u{0330}' => Combining(r"\textsubtilde"), // tipa package
+        '\u{0331}' => Combining(r"\b"),
+        '\u{0332}' => Combining(r"\b"),
+        '\u{0361}' => CombiningDouble(r"\t"),
         '“' => Escape(r"``"),
         '”' => Escape(r"''"),
         '‘' => Escape(r"`"),
@@ -57,47 +95,63 @@ fn classify_char(c: char) -> CharKind {
         '‐' => Escape(r"-"),
         '–' => Escape(r"--"),
         '—' => Escape(r"---"),
-        c => {
-            error!(char=%c.escape_unicode(), "unescaped unicode character");
-            if c == '\u{FFFD}' {
-                panic!(
-                    "Encountered `�`: the Unicode replacement character.  
-                    This indicates  the original doi.org source is fucked up and you'll have to fill in this field manually."
-                )
-            }
-            panic!(
-                "unimplemented: not sure how to interpret {}: {} ",
-                c.escape_unicode(),
-                c
-            )
-        }
+        _ => return None,
+    };
+    Some(kind)
+}
+
+fn unknown_char_error(c: char) -> anyhow::Error {
+    if c == '\u{FFFD}' {
+        anyhow!(
+            "Encountered `�`: the Unicode replacement character.  \
+            This indicates the original doi.org source is broken and you'll have to fill in this field manually."
+        )
+    } else {
+        anyhow!(
+            "unimplemented: not sure how to interpret {}: {}",
+            c.escape_unicode(),
+            c
+        )
     }
 }
 
 #[must_use = "must call .finish()"]
 struct CharEscaper<'a, 'b> {
     formatter: &'a mut Formatter<'b>,
-    ch: CharKind,
+    /// The current glyph, which is `None` until the first non-combining character
+    ch: Option<CharKind>,
     closing_brackets: u32,
+    /// Brackets to close after the next glyph
+    closing_brackets_next: u32,
 }
 
 impl<'a, 'b> CharEscaper<'a, 'b> {
-    fn new(first: CharKind, f: &'a mut Formatter<'b>) -> Self {
-        if matches!(&first, CharKind::Combining(_)) {
-            panic!("first character should not be combining character");
-        }
+    fn new(f: &'a mut Formatter<'b>) -> Self {
         CharEscaper {
             formatter: f,
-            ch: first,
+            ch: None,
             closing_brackets: 0,
+            closing_brackets_next: 0,
         }
     }
 
     fn finish_current_glyph(&mut self) -> FmtResult {
         match self.ch {
-            CharKind::Verbatim(c) => self.formatter.write_char(c)?,
-            CharKind::Escape(s) => self.formatter.write_str(s)?,
-            CharKind::Combining(_) => unreachable!(),
+            Some(CharKind::Verbatim(c)) => self.formatter.write_char(c)?,
+            Some(CharKind::Escape(s)) => {
+                // A glyph which is already a group, like `{\i}`, doesn't need another one when
+                // it is under an accent: `\'{\i}` rather than `\'{{\i}}`
+                let s = match self.closing_brackets {
+                    0 => s,
+                    _ => s
+                        .strip_prefix('{')
+                        .and_then(|s| s.strip_suffix('}'))
+                        .unwrap_or(s),
+                };
+                self.formatter.write_str(s)?
+            }
+            None => {}
+            Some(CharKind::Combining(_) | CharKind::CombiningDouble(_)) => unreachable!(),
         }
         for _ in 0..self.closing_brackets {
             self.formatter.write_char('}')?;
@@ -106,36 +160,96 @@ impl<'a, 'b> CharEscaper<'a, 'b> {
     }
 
     fn write_char(&mut self, c: CharKind) -> FmtResult {
-        if let CharKind::Combining(s) = c {
-            self.formatter.write_str(s)?;
-            self.formatter.write_char('{')?;
-            self.closing_brackets += 1;
-        } else {
-            self.finish_current_glyph();
-            self.ch = c;
-            self.closing_brackets = 0;
+        match c {
+            CharKind::CombiningDouble(s) if self.ch.is_some() => {
+                self.formatter.write_str(s)?;
+                self.formatter.write_char('{')?;
+                self.closing_brackets_next += 1;
+            }
+            // A leading double mark has no previous glyph to join, so like any other leading
+            // mark it goes over an empty group
+            CharKind::Combining(s) | CharKind::CombiningDouble(s) => {
+                self.formatter.write_str(s)?;
+                self.formatter.write_char('{')?;
+                self.closing_brackets += 1;
+            }
+            c => {
+                self.finish_current_glyph()?;
+                self.ch = Some(c);
+                self.closing_brackets = std::mem::take(&mut self.closing_brackets_next);
+            }
         }
         Ok(())
     }
 
     fn finish(mut self) -> Result<&'a mut Formatter<'b>, FmtError> {
         self.finish_current_glyph()?;
+        for _ in 0..self.closing_brackets_next {
+            self.formatter.write_char('}')?;
+        }
         Ok(self.formatter)
     }
 }
 
+impl<'a> Utf8ToTex<'a> {
+    /// Set the policy for characters which have no known LaTeX equivalent.
+    pub fn on_unknown(self, policy: UnknownCharPolicy) -> Self {
+        Utf8ToTex {
+            on_unknown: policy,
+            ..self
+        }
+    }
+
+    /// Returns an error describing the first character which has no known LaTeX equivalent, if any.
+    pub fn check(&self) -> Result<()> {
+        match self
+            .original
+            .chars()
+            .nfkd()
+            .find(|&c| classify_char(c).is_none())
+        {
+            Some(c) => Err(unknown_char_error(c)),
+            None => Ok(()),
+        }
+    }
+
+    fn classify(&self, c: char) -> Result<CharKind, FmtError> {
+        if let Some(kind) = classify_char(c) {
+            return Ok(kind);
+        }
+        match self.on_unknown {
+            UnknownCharPolicy::Passthrough => Ok(CharKind::Verbatim(c)),
+            UnknownCharPolicy::Substitute => {
+                warn!(char=%c.escape_unicode(), "substituting unknown unicode character");
+                Ok(CharKind::Verbatim('?'))
+            }
+            UnknownCharPolicy::Error => {
+                error!(char=%c.escape_unicode(), "{}", unknown_char_error(c));
+                Err(FmtError)
+            }
+        }
+    }
+}
+
 impl<'a> Display for Utf8ToTex<'a> {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        let mut chars = self.original.chars().nfkd().map(classify_char);
-
-        let mut closing_brackets = 0;
-        let mut e = match chars.next() {
-            Some(c) => CharEscaper::new(c, f),
-            None => return Ok(()),
-        };
+        let mut chars = self
+            .original
+            .chars()
+            .nfkd()
+            .map(|c| self.classify(c))
+            .peekable();
+
+        if let Some(Ok(CharKind::Combining(_) | CharKind::CombiningDouble(_))) = chars.peek() {
+            warn!(
+                string = self.original,
+                "string starts with a combining character, placing it over an empty group"
+            );
+        }
 
+        let mut e = CharEscaper::new(f);
         for c in chars {
-            e.write_char(c)?;
+            e.write_char(c?)?;
         }
         e.finish()?;
         Ok(())
@@ -144,7 +258,10 @@ impl<'a> Display for Utf8ToTex<'a> {
 
 /// Substitute non-ASCII characters and escape TeX control characters.
 pub fn utf8_to_tex(s: &str) -> Utf8ToTex {
-    Utf8ToTex { original: s }
+    Utf8ToTex {
+        original: s,
+        on_unknown: UnknownCharPolicy::default(),
+    }
 }
 
 #[cfg(test)]
@@ -156,6 +273,13 @@ mod tests {
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn leading_combining() {
+        cmp("\u{0301}a", r"\'{}a");
+        cmp("\u{0361}oo", r"\t{}oo");
+        cmp("\u{0328}\u{0361}oo", r"\k{\t{}}oo");
+    }
+
     #[test]
     fn acute() {
         cmp("É", r"\'{E}");
@@ -241,6 +365,38 @@ mod tests {
         cmp("Ø", r"\O{}");
     }
 
+    #[test]
+    fn ash() {
+        cmp("æ", r"\ae{}");
+        cmp("Æ", r"\AE{}");
+        cmp("Ærø", r"\AE{}r\o{}");
+    }
+
+    #[test]
+    fn ethel() {
+        cmp("œ", r"\oe{}");
+        cmp("Œ", r"\OE{}");
+    }
+
+    #[test]
+    fn sharp_s() {
+        cmp("ß", r"\ss{}");
+        cmp("ẞ", r"\SS{}");
+        cmp("Straße", r"Stra\ss{}e");
+    }
+
+    #[test]
+    fn barred_d() {
+        cmp("đ", r"\dj{}");
+        cmp("Đ", r"\DJ{}");
+    }
+
+    #[test]
+    fn thorn() {
+        cmp("þ", r"\th{}");
+        cmp("Þ", r"\TH{}");
+    }
+
     #[test]
     fn dotless_i() {
         cmp("ı", r"{\i}");
@@ -253,6 +409,158 @@ mod tests {
         cmp("\u{212b}\u{0300}\u{0301}", r"\r{\`{\'{A}}}");
     }
 
+    /// Whether the unescaped braces in `s` are balanced
+    fn balanced(s: &str) -> bool {
+        let mut depth = 0i32;
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            if depth < 0 {
+                return false;
+            }
+        }
+        depth == 0
+    }
+
+    #[test]
+    fn combining_over_escape() {
+        cmp("&\u{0301}", r"\'{\&}");
+        cmp("{\u{0302}\u{0304}", r"\^{\={\{}}");
+        cmp("\\\u{0301}x", r"\'{\textbackslash{}}x");
+        cmp("ı\u{0301}", r"\'{\i}");
+        cmp("ı", r"{\i}");
+        cmp("o\u{0361}&", r"\t{o\&}");
+        cmp("&\u{0361}o", r"\t{\&o}");
+
+        let escaped = "\\~#$%&_{}^łøıæßþ“”‘’–—";
+        let marks = [
+            "\u{0301}",
+            "\u{0302}\u{0304}",
+            "\u{0361}",
+            "\u{0328}\u{0361}",
+        ];
+        for c in escaped.chars() {
+            for m in marks {
+                for input in [
+                    format!("{}{}", c, m),
+                    format!("{}{}{}", c, m, c),
+                    format!("{}{}{}", m, c, m),
+                    format!("a{}{}{}b", c, m, m),
+                ] {
+                    let output = utf8_to_tex(&input).to_string();
+                    assert!(balanced(&output), "{:?} -> {:?}", input, output);
+                }
+            }
+        }
+    }
+
+    /// Random strings which mix ASCII, accented letters, combining marks and arbitrary characters
+    #[test]
+    fn fuzz_balanced() {
+        // xorshift64, so the test is deterministic without an RNG dependency
+        let mut state = 0x9e3779b97f4a7c15_u64;
+        let mut next = move |n: u32| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n as u64) as u32
+        };
+        let ranges = [
+            (0x20, 0x7f),
+            (0xa0, 0x250),
+            (0x300, 0x370),
+            (0x1e00, 0x1f00),
+            (0x2000, 0x2070),
+            (0, 0x30000),
+        ];
+
+        for _ in 0..2000 {
+            let len = next(12);
+            let input: String = (0..len)
+                .filter_map(|_| {
+                    let (lo, hi) = ranges[next(ranges.len() as u32) as usize];
+                    char::from_u32(lo + next(hi - lo))
+                })
+                .collect();
+            let output = utf8_to_tex(&input)
+                .on_unknown(UnknownCharPolicy::Passthrough)
+                .to_string();
+            assert!(balanced(&output), "{:?} -> {:?}", input, output);
+        }
+    }
+
+    #[test]
+    fn double_grave() {
+        cmp("ȁ", r"\textdoublegrave{a}")
+    }
+
+    #[test]
+    fn hook_above() {
+        cmp("ả", r"\h{a}")
+    }
+
+    #[test]
+    fn inverted_breve() {
+        cmp("ȃ", r"\textroundcap{a}")
+    }
+
+    #[test]
+    fn overline() {
+        cmp("a\u{0305}", r"\={a}")
+    }
+
+    #[test]
+    fn umlaut_below() {
+        cmp("ṳ", r"\textsubumlaut{u}")
+    }
+
+    #[test]
+    fn ring_below() {
+        cmp("ḁ", r"\textsubring{a}")
+    }
+
+    #[test]
+    fn tilde_below() {
+        cmp("ḭ", r"\textsubtilde{i}")
+    }
+
+    #[test]
+    fn macron_below() {
+        cmp("ḏ", r"\b{d}");
+        cmp("ṯ", r"\b{t}");
+        cmp("d\u{0332}", r"\b{d}");
+    }
+
+    #[test]
+    fn tie() {
+        cmp("o\u{0361}o", r"\t{oo}");
+        cmp("t\u{0361}s", r"\t{ts}");
+        cmp("xt\u{0361}sx", r"x\t{ts}x");
+        cmp("o\u{0361}", r"\t{o}");
+    }
+
+    #[test]
+    fn unknown_char_policy() {
+        use std::fmt::Write;
+        let s = "foo 漢字 bar";
+        let escape = |policy| {
+            let mut out = String::new();
+            write!(out, "{}", utf8_to_tex(s).on_unknown(policy)).map(|_| out)
+        };
+        assert_eq!(escape(UnknownCharPolicy::Passthrough).unwrap(), s);
+        assert_eq!(escape(UnknownCharPolicy::Substitute).unwrap(), "foo ?? bar");
+        assert!(escape(UnknownCharPolicy::Error).is_err());
+        assert!(utf8_to_tex(s).check().is_err());
+        assert!(utf8_to_tex("foo bar").check().is_ok());
+    }
+
     #[test]
     fn quotes() {
         cmp("’", "'");
@@ -14,7 +14,6 @@ macro_rules! bail {
     };
 }
 
-pub use clap::{Args, Parser};
 pub use tracing::{
     debug, debug_span, error, error_span, info, info_span, instrument, trace, trace_span, warn,
     warn_span,
@@ -23,59 +22,46 @@ pub use tracing::{
 mod escape;
 pub use escape::*;
 
-use serde::{de::DeserializeOwned, Serialize};
+mod markup;
+pub use markup::*;
 
 pub mod biblatex;
 
-use posix_cli_utils::IoContext;
+pub mod crossref;
 
-pub fn read_json<T, P>(path: P) -> Result<T>
-where
-    T: DeserializeOwned,
-    P: AsRef<Path>,
-{
-    let path = path.as_ref();
-    let f = std::fs::File::open(path).context_read(&path)?;
-    let val = serde_json::from_reader(f)?;
-    Ok(val)
-}
+#[cfg(feature = "bin")]
+mod cli;
+#[cfg(feature = "bin")]
+pub use cli::*;
 
-pub fn write_json<T, P>(path: P, val: T) -> Result<()>
-where
-    T: Serialize,
-    P: AsRef<Path>,
-{
-    let path = path.as_ref();
-    let f = std::fs::File::create(path).context_write(&path)?;
-    serde_json::to_writer(f, &val)?;
-    Ok(())
+/// Returns `true` if `path` has a `.yaml` or `.yml` extension.
+pub fn is_yaml(path: impl AsRef<Path>) -> bool {
+    matches!(
+        path.as_ref().extension().and_then(|e| e.to_str()),
+        Some("yaml" | "yml")
+    )
 }
 
-pub fn write_json_pretty<T, P>(path: P, val: T) -> Result<()>
-where
-    T: Serialize,
-    P: AsRef<Path>,
-{
-    let path = path.as_ref();
-    let f = std::fs::File::create(path).context_write(&path)?;
-    serde_json::to_writer_pretty(f, &val)?;
-    Ok(())
+/// Returns `true` if `path` is `-`, which by convention means STDIN or STDOUT.
+pub fn is_stdio(path: impl AsRef<Path>) -> bool {
+    path.as_ref() == Path::new("-")
 }
 
-pub fn logging_init() {
-    use tracing_subscriber::{filter::LevelFilter, fmt, prelude::*, EnvFilter};
-
-    tracing_subscriber::registry()
-        .with(fmt::layer().with_writer(std::io::stderr).without_time())
-        .with(
-            EnvFilter::builder()
-                .with_default_directive(LevelFilter::INFO.into())
-                .from_env_lossy(),
-        )
-        .init();
-}
-
-pub fn logging_init_test() {
-    use tracing_subscriber::{fmt, prelude::*};
-    tracing_subscriber::fmt().without_time().try_init().ok();
+/// Infer the CSL type for the out-of-spec types used by Crossref and other DOI services, or
+/// `None` if `ty` should be kept.  This is a heuristic, so a warning is logged whenever a type is
+/// changed:
+///
+/// - `journal-article` becomes `article-journal`.
+/// - `proceedings-article` becomes `paper-conference`.
+/// - `book-chapter` becomes `paper-conference`, not `book` as you might expect, because Crossref
+///   lists most conference papers as book chapters.  Genuine book chapters will be mistyped.
+pub fn infer_csl_type(ty: &str) -> Option<&'static str> {
+    let inferred = match ty {
+        "book-chapter" => "paper-conference",
+        "journal-article" => "article-journal",
+        "proceedings-article" => "paper-conference",
+        _ => return None,
+    };
+    warn!(invalid=%ty, %inferred, "converting out-of-spec type, inferred type may be wrong");
+    Some(inferred)
 }
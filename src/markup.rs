@@ -0,0 +1,128 @@
+use std::borrow::Cow;
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+lazy_static! {
+    static ref ENTITY: Regex = Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[A-Za-z]+);").unwrap();
+    static ref TAG: Regex = Regex::new(r"<(/?)([A-Za-z][\w:.-]*)(?:\s[^<>]*)?(/?)>").unwrap();
+}
+
+/// Tags which separate blocks of text, and are replaced by a space rather than removed
+const BLOCK_TAGS: &[&str] = &["br", "list", "list-item", "p", "sec", "title"];
+
+/// HTML and JATS formatting tags, and the CSL rich text tag each is kept as
+const FORMATTING_TAGS: &[(&str, &str)] = &[
+    ("b", "b"),
+    ("bold", "b"),
+    ("em", "i"),
+    ("i", "i"),
+    ("italic", "i"),
+    ("strong", "b"),
+    ("sub", "sub"),
+    ("sup", "sup"),
+];
+
+fn named_entity(name: &str) -> Option<char> {
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "ndash" => '–',
+        "mdash" => '—',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "hellip" => '…',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// The character a reference like `amp`, `#8211` or `#x2013` (without the `&` and `;`) stands for
+fn decode_entity(name: &str) -> Option<char> {
+    match name.strip_prefix('#') {
+        Some(n) => match n.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => n.parse().ok(),
+        }
+        .and_then(char::from_u32),
+        None => named_entity(name),
+    }
+}
+
+/// Decode HTML/XML character references, such as `&amp;`, `&#8211;` and `&#x2013;`.  Unknown
+/// named references are left alone.
+pub fn decode_entities(s: &str) -> Cow<'_, str> {
+    ENTITY.replace_all(s, |c: &Captures| match decode_entity(&c[1]) {
+        Some(ch) => ch.to_string(),
+        None => c[0].to_string(),
+    })
+}
+
+/// Remove HTML, MathML and JATS tags, decode character references and collapse whitespace.
+/// Block-level tags like `<jats:p>` become spaces, so paragraphs don't run together.  Italics,
+/// bold, subscripts and superscripts are kept as the CSL rich text tags `<i>`, `<b>`, `<sub>` and
+/// `<sup>`.  Escaped angle brackets are kept as `&lt;` and `&gt;`, so that escaped text such as
+/// `&lt;i&gt;` can't turn into a tag.
+pub fn strip_markup(s: &str) -> String {
+    let stripped = TAG.replace_all(s, |c: &Captures| {
+        let name = c[2].rsplit(':').next().unwrap().to_ascii_lowercase();
+        if BLOCK_TAGS.contains(&name.as_str()) {
+            return " ".to_string();
+        }
+        let self_closing = !c[3].is_empty();
+        match FORMATTING_TAGS.iter().find(|(n, _)| *n == name) {
+            Some((_, csl)) if !self_closing => format!("<{}{}>", &c[1], csl),
+            _ => String::new(),
+        }
+    });
+    let decoded = ENTITY.replace_all(&stripped, |c: &Captures| match decode_entity(&c[1]) {
+        Some('<') => "&lt;".to_string(),
+        Some('>') => "&gt;".to_string(),
+        Some(ch) => ch.to_string(),
+        None => c[0].to_string(),
+    });
+    decoded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entities() {
+        assert_eq!(decode_entities("A &amp; B"), "A & B");
+        assert_eq!(decode_entities("1&#8211;2 &#x2013; 3"), "1–2 – 3");
+        assert_eq!(decode_entities("&lt;x&gt; &unknown;"), "<x> &unknown;");
+    }
+
+    #[test]
+    fn markup() {
+        assert_eq!(
+            strip_markup("<jats:p>First.</jats:p><jats:p>Second &amp; last.</jats:p>"),
+            "First. Second & last."
+        );
+        assert_eq!(
+            strip_markup("Na<sub>2</sub>CO<sub>3</sub>"),
+            "Na<sub>2</sub>CO<sub>3</sub>"
+        );
+        assert_eq!(
+            strip_markup("<jats:italic>In  vivo</jats:italic>\n study"),
+            "<i>In vivo</i> study"
+        );
+        assert_eq!(
+            strip_markup("<mml:math><mml:mi>x</mml:mi></mml:math> <B class=\"c\">y</B>"),
+            "x <b>y</b>"
+        );
+        assert_eq!(strip_markup("x < y and y > z"), "x < y and y > z");
+        assert_eq!(
+            strip_markup("&lt;i&gt;x&#60;/i&#x3E; &amp; y"),
+            "&lt;i&gt;x&lt;/i&gt; & y"
+        );
+    }
+}